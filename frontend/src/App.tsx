@@ -23,8 +23,16 @@ type Campaign = {
   budget_total_cents: number;
   budget_remaining_cents: number;
   query_urls: string[];
+  task_valid_for_secs: number | null;
+  created_by: string | null;
   active: boolean;
   created_at: string;
+  last_used_at: string | null;
+  budget_exhausted_webhook_url: string | null;
+  estimated_calls_remaining: number;
+  estimated_total_calls: number;
+  utilization: number;
+  incomplete_task_behavior: "require_task_or_pay" | "skip_if_incomplete";
 };
 
 type Profile = {
@@ -70,32 +78,50 @@ type SponsoredApi = {
   budget_total_cents: number;
   budget_remaining_cents: number;
   active: boolean;
+  charge_on_upstream_error: boolean;
+  success_status_codes: number[];
+  allowed_response_content_types: string[];
+  created_by: string | null;
   created_at: string;
+  last_used_at: string | null;
+  budget_exhausted_webhook_url: string | null;
+  x402_scheme: "exact" | "upto" | null;
+  input_schema: Record<string, unknown> | boolean | null;
+  output_schema: Record<string, unknown> | boolean | null;
+  follow_redirects: boolean;
+  utilization: number;
+  distinct_callers?: number;
 };
 
 type PaymentRequired = {
   service: string;
   amount_cents: number;
+  x402_version: string;
   accepted_header: string;
   payment_required: string;
   message: string;
   next_step: string;
 };
 
+type PaymentMode = 'user_direct' | 'sponsored';
+
 type ServiceRunResponse = {
   service: string;
   output: string;
-  payment_mode: string;
+  payment_mode: PaymentMode;
   sponsored_by: string | null;
+  campaign_id: string | null;
   tx_hash: string | null;
 };
 
 type SponsoredApiRunResponse = {
   api_id: string;
-  payment_mode: string;
+  payment_mode: PaymentMode;
   sponsored_by: string | null;
   tx_hash: string | null;
+  charged: boolean;
   upstream_status: number;
+  upstream_duration_ms: number;
   upstream_body: string;
 };
 
@@ -0,0 +1,9 @@
+create table if not exists campaign_idempotency_keys (
+  idempotency_key text primary key,
+  campaign_id uuid not null references campaigns(id) on delete cascade,
+  request jsonb not null,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists campaign_idempotency_keys_campaign_id_idx
+  on campaign_idempotency_keys(campaign_id);
@@ -0,0 +1,5 @@
+alter table payments
+  add column if not exists user_id uuid references users(id) on delete set null;
+
+create index if not exists payments_user_id_idx
+  on payments(user_id);
@@ -0,0 +1,2 @@
+alter table campaigns
+  add column if not exists task_valid_for_secs bigint;
@@ -0,0 +1,6 @@
+alter table payments
+  add column if not exists network text,
+  add column if not exists scheme text;
+
+create index if not exists payments_network_scheme_idx
+  on payments(network, scheme);
@@ -0,0 +1,2 @@
+alter table payments
+  add column if not exists caller text;
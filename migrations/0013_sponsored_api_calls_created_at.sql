@@ -0,0 +1,2 @@
+create index if not exists sponsored_api_calls_api_id_created_at_idx
+  on sponsored_api_calls(sponsored_api_id, created_at desc);
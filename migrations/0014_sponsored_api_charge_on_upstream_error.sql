@@ -0,0 +1,2 @@
+alter table sponsored_apis
+  add column if not exists charge_on_upstream_error boolean not null default false;
@@ -0,0 +1,5 @@
+alter table campaigns
+  add column if not exists created_by text;
+
+alter table sponsored_apis
+  add column if not exists created_by text;
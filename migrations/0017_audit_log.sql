@@ -0,0 +1,16 @@
+create table if not exists audit_log (
+  id uuid primary key,
+  actor text,
+  action text not null,
+  entity_type text not null,
+  entity_id uuid not null,
+  before jsonb,
+  after jsonb,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists audit_log_entity_idx
+  on audit_log(entity_type, entity_id, created_at desc);
+
+create index if not exists audit_log_created_at_idx
+  on audit_log(created_at desc);
@@ -0,0 +1,2 @@
+alter table sponsored_apis
+  add column if not exists allowed_response_content_types text[] not null default '{}';
@@ -0,0 +1,5 @@
+alter table campaigns
+  add column if not exists last_used_at timestamptz;
+
+alter table sponsored_apis
+  add column if not exists last_used_at timestamptz;
@@ -0,0 +1,5 @@
+alter table campaigns
+  add column if not exists budget_exhausted_webhook_url text;
+
+alter table sponsored_apis
+  add column if not exists budget_exhausted_webhook_url text;
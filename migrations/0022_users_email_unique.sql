@@ -0,0 +1,43 @@
+-- Emails are compared trimmed and case-insensitively. Profiles that already share an email
+-- are merged into the oldest one before the index is built: it gains their roles and tools,
+-- their task completions and payments move to it, and the newer profiles are removed.
+create temporary table user_email_merges on commit drop as
+select id as duplicate_id,
+       first_value(id) over (partition by lower(btrim(email)) order by created_at, id) as keeper_id
+from users;
+
+delete from user_email_merges where duplicate_id = keeper_id;
+
+update users keeper
+set roles = array(
+      select unnest(keeper.roles)
+      union
+      select unnest(u.roles)
+      from users u
+      join user_email_merges m on m.duplicate_id = u.id
+      where m.keeper_id = keeper.id
+    ),
+    tools_used = array(
+      select unnest(keeper.tools_used)
+      union
+      select unnest(u.tools_used)
+      from users u
+      join user_email_merges m on m.duplicate_id = u.id
+      where m.keeper_id = keeper.id
+    )
+where keeper.id in (select keeper_id from user_email_merges);
+
+update task_completions t
+set user_id = m.keeper_id
+from user_email_merges m
+where t.user_id = m.duplicate_id;
+
+update payments p
+set user_id = m.keeper_id
+from user_email_merges m
+where p.user_id = m.duplicate_id;
+
+delete from users where id in (select duplicate_id from user_email_merges);
+
+create unique index if not exists users_email_key
+  on users (lower(btrim(email)));
@@ -0,0 +1,11 @@
+create table if not exists proxy_decisions (
+  id uuid primary key,
+  decision text not null,
+  user_id uuid references users(id) on delete set null,
+  service text not null,
+  campaign_id uuid references campaigns(id) on delete set null,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists proxy_decisions_created_at_idx
+  on proxy_decisions(created_at);
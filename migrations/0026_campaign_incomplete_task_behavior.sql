@@ -0,0 +1,3 @@
+alter table campaigns
+  add column if not exists incomplete_task_behavior text not null default 'require_task_or_pay'
+    check (incomplete_task_behavior in ('require_task_or_pay', 'skip_if_incomplete'));
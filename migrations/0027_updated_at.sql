@@ -0,0 +1,26 @@
+-- Bumped by trigger rather than by each update statement, so budget debits, pauses, and
+-- manual edits all count as changes for conditional GETs.
+alter table campaigns
+  add column if not exists updated_at timestamptz not null default now();
+alter table sponsored_apis
+  add column if not exists updated_at timestamptz not null default now();
+
+update campaigns set updated_at = greatest(created_at, last_used_at);
+update sponsored_apis set updated_at = greatest(created_at, last_used_at);
+
+create or replace function set_updated_at() returns trigger as $$
+begin
+  new.updated_at = now();
+  return new;
+end;
+$$ language plpgsql;
+
+drop trigger if exists campaigns_set_updated_at on campaigns;
+create trigger campaigns_set_updated_at
+  before update on campaigns
+  for each row execute function set_updated_at();
+
+drop trigger if exists sponsored_apis_set_updated_at on sponsored_apis;
+create trigger sponsored_apis_set_updated_at
+  before update on sponsored_apis
+  for each row execute function set_updated_at();
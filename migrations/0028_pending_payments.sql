@@ -0,0 +1,17 @@
+-- Async settlement (X402_SETTLE_MODE=async) keeps each queued payment as a `pending` row until
+-- the facilitator settles or rejects it, so a restart or a facilitator outage cannot lose it.
+-- `settlement_job` holds what the reconciler needs to retry the settle call; the settled or
+-- failed row that resolves a payment replaces its pending row.
+alter table payments
+  drop constraint if exists payments_status_check;
+
+alter table payments
+  add constraint payments_status_check check (status in ('settled', 'failed', 'pending'));
+
+alter table payments
+  add column if not exists settlement_job jsonb,
+  add column if not exists settle_attempts integer not null default 0;
+
+create index if not exists payments_pending_created_at_idx
+  on payments(created_at)
+  where status = 'pending';
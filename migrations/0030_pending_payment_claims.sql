@@ -0,0 +1,5 @@
+-- A reconciliation pass claims the pending rows it is about to settle, so two instances (or
+-- two overlapping passes) never send the same settle call. A claim older than
+-- X402_RECONCILE_AFTER_SECS is treated as abandoned, e.g. by an instance that crashed mid-pass.
+alter table payments
+  add column if not exists settle_claimed_at timestamptz;
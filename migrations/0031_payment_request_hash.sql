@@ -0,0 +1,4 @@
+-- A sponsored proxy payment made under an Idempotency-Key stores a hash of the run's input, so
+-- reusing the key for a different input is rejected instead of replayed for free.
+alter table payments
+  add column if not exists request_hash text;
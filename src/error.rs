@@ -1,13 +1,16 @@
 use axum::{
     Json,
-    http::{HeaderName, HeaderValue, StatusCode},
+    http::{HeaderName, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use serde_json::Value;
 use thiserror::Error;
 
-use crate::types::{PAYMENT_REQUIRED_HEADER, PaymentRequired, X402_VERSION_HEADER};
+use crate::{
+    types::{DB_RETRY_AFTER_SECS, PAYMENT_REQUIRED_HEADER, PaymentRequired},
+    utils::insert_x402_version_header,
+};
 
 pub type ApiResult<T> = Result<T, ApiError>;
 
@@ -27,15 +30,21 @@ struct ErrorBody {
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("payment required")]
-    PaymentRequired(PaymentRequired),
+    PaymentRequired(Box<PaymentRequired>),
     #[error("{message}")]
     Http {
         status: StatusCode,
         code: String,
         message: String,
+        details: Option<Value>,
     },
+    #[error("{message}")]
+    PaymentRejected { message: String },
     #[error("database error: {message}")]
     Database { status: StatusCode, message: String },
+    /// No pooled connection freed up in time; transient backpressure rather than a bug.
+    #[error("database busy: {message}")]
+    DatabaseUnavailable { message: String },
     #[error("upstream error: {message}")]
     Upstream { status: StatusCode, message: String },
     #[error("config error: {message}")]
@@ -45,11 +54,22 @@ pub enum ApiError {
 }
 
 impl ApiError {
+    /// Attaches structured details to errors whose body can carry them.
+    pub fn with_details(mut self, value: Value) -> Self {
+        match &mut self {
+            Self::PaymentRequired(payload) => payload.details = Some(value),
+            Self::Http { details, .. } => *details = Some(value),
+            _ => {}
+        }
+        self
+    }
+
     pub fn not_found(message: impl Into<String>) -> Self {
         Self::Http {
             status: StatusCode::NOT_FOUND,
             code: "not_found".to_string(),
             message: message.into(),
+            details: None,
         }
     }
 
@@ -58,6 +78,34 @@ impl ApiError {
             status: StatusCode::BAD_REQUEST,
             code: "validation_error".to_string(),
             message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn validation_field(field: &str, message: impl Into<String>) -> Self {
+        Self::Http {
+            status: StatusCode::BAD_REQUEST,
+            code: "validation_error".to_string(),
+            message: message.into(),
+            details: Some(serde_json::json!({ "field": field })),
+        }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Http {
+            status: StatusCode::UNAUTHORIZED,
+            code: "unauthorized".to_string(),
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Http {
+            status: StatusCode::CONFLICT,
+            code: "conflict".to_string(),
+            message: message.into(),
+            details: None,
         }
     }
 
@@ -66,6 +114,13 @@ impl ApiError {
             status: StatusCode::PRECONDITION_REQUIRED,
             code: "precondition_required".to_string(),
             message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn payment_rejected(message: impl Into<String>) -> Self {
+        Self::PaymentRejected {
+            message: message.into(),
         }
     }
 
@@ -76,6 +131,16 @@ impl ApiError {
         }
     }
 
+    /// Maps a query error to a 500, except pool acquire timeouts which become a retryable 503.
+    pub fn from_sqlx(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::PoolTimedOut => Self::DatabaseUnavailable {
+                message: err.to_string(),
+            },
+            other => Self::database(StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+        }
+    }
+
     pub fn upstream(status: StatusCode, message: impl Into<String>) -> Self {
         Self::Upstream {
             status,
@@ -89,17 +154,34 @@ impl ApiError {
         }
     }
 
+    /// A `config_error` naming what cannot work without `DATABASE_URL`, e.g. "creating a
+    /// campaign".
+    pub fn database_required(operation: &str) -> Self {
+        Self::config(format!("{operation} requires Postgres; set DATABASE_URL"))
+    }
+
     pub fn internal(message: impl Into<String>) -> Self {
         Self::Internal {
             message: message.into(),
         }
     }
 
+    /// The status and `error` object this error responds with, for reporting it as one entry of
+    /// a larger response.
+    pub fn status_and_body(&self) -> (StatusCode, Value) {
+        (
+            self.status_code(),
+            serde_json::to_value(self.body()).unwrap_or_default(),
+        )
+    }
+
     fn status_code(&self) -> StatusCode {
         match self {
             Self::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
             Self::Http { status, .. } => *status,
+            Self::PaymentRejected { .. } => StatusCode::PAYMENT_REQUIRED,
             Self::Database { status, .. } => *status,
+            Self::DatabaseUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
             Self::Upstream { status, .. } => *status,
             Self::Config { .. } | Self::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -112,9 +194,19 @@ impl ApiError {
                 message: "payment required".to_string(),
                 details: None,
             },
-            Self::Http { code, message, .. } => ErrorBody {
+            Self::Http {
+                code,
+                message,
+                details,
+                ..
+            } => ErrorBody {
                 code: code.clone(),
                 message: message.clone(),
+                details: details.clone(),
+            },
+            Self::PaymentRejected { message } => ErrorBody {
+                code: "payment_rejected".to_string(),
+                message: message.clone(),
                 details: None,
             },
             Self::Database { message, .. } => ErrorBody {
@@ -122,6 +214,11 @@ impl ApiError {
                 message: message.clone(),
                 details: None,
             },
+            Self::DatabaseUnavailable { message } => ErrorBody {
+                code: "database_unavailable".to_string(),
+                message: message.clone(),
+                details: None,
+            },
             Self::Upstream { message, .. } => ErrorBody {
                 code: "upstream_error".to_string(),
                 message: message.clone(),
@@ -144,13 +241,20 @@ impl ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
-            ApiError::PaymentRequired(payload) => payment_required_response(payload),
+            ApiError::PaymentRequired(payload) => payment_required_response(*payload),
             other => {
                 let status = other.status_code();
+                let retry_after = matches!(other, ApiError::DatabaseUnavailable { .. });
                 let body = ErrorResponse {
                     error: other.body(),
                 };
-                (status, Json(body)).into_response()
+                let mut response = (status, Json(body)).into_response();
+                if retry_after {
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(DB_RETRY_AFTER_SECS));
+                }
+                response
             }
         }
     }
@@ -158,11 +262,9 @@ impl IntoResponse for ApiError {
 
 fn payment_required_response(payload: PaymentRequired) -> Response {
     let payment_required = payload.payment_required.clone();
+    let x402_version = payload.x402_version.clone();
     let mut response = (StatusCode::PAYMENT_REQUIRED, Json(payload)).into_response();
-    response.headers_mut().insert(
-        HeaderName::from_static(X402_VERSION_HEADER),
-        HeaderValue::from_static("2"),
-    );
+    insert_x402_version_header(response.headers_mut(), &x402_version);
     if let Ok(header_value) = HeaderValue::from_str(&payment_required) {
         response.headers_mut().insert(
             HeaderName::from_static(PAYMENT_REQUIRED_HEADER),
@@ -1,36 +1,83 @@
 mod error;
 mod onchain;
+mod schema;
 mod types;
 mod utils;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header},
     response::{IntoResponse, Response},
     routing::{get, post},
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use prometheus::{Encoder, TextEncoder};
 use sqlx::types::Json as DbJson;
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::RwLock;
-use tower_http::cors::{AllowOrigin, Any, CorsLayer};
-use tracing::info;
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    timeout::TimeoutLayer,
+};
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::error::{ApiError, ApiResult};
+use crate::onchain::probe_facilitator;
 use crate::types::*;
 use crate::utils::*;
 
-fn build_app(state: SharedState) -> Router {
+fn build_app(state: SharedState, request_timeout: Duration) -> Router {
+    api_routes()
+        .layer(request_timeout_layer(request_timeout))
+        .merge(ops_routes())
+        .layer(cors_layer_from_env())
+        .with_state(state)
+}
+
+/// Splits the API from `/metrics` and `/health` so the latter can bind to a private port.
+fn build_split_apps(state: SharedState, request_timeout: Duration) -> (Router, Router) {
+    let api = api_routes()
+        .layer(request_timeout_layer(request_timeout))
+        .layer(cors_layer_from_env())
+        .with_state(state.clone());
+    let ops = ops_routes().with_state(state);
+    (api, ops)
+}
+
+fn ops_routes() -> Router<SharedState> {
     Router::new()
         .route("/health", get(health))
+        .route("/metrics", get(prometheus_metrics))
+}
+
+fn api_routes() -> Router<SharedState> {
+    Router::new()
         .route("/profiles", post(create_profile).get(list_profiles))
         .route("/register", post(register_user))
+        .route("/users:batch", post(create_profiles_batch))
+        .route("/users/lookup", post(lookup_user))
+        .route("/users/{user_id}", get(get_user))
+        .route("/users/{user_id}/spend", get(user_spend))
         .route("/campaigns", post(create_campaign).get(list_campaigns))
         .route("/campaigns/discovery", get(list_campaign_discovery))
         .route("/campaigns/{campaign_id}", get(get_campaign))
+        .route("/campaigns/{campaign_id}/pause", post(pause_campaign))
+        .route("/campaigns/{campaign_id}/resume", post(resume_campaign))
+        .route(
+            "/campaigns/{campaign_id}/completions",
+            get(list_campaign_completions),
+        )
+        .route(
+            "/campaigns/{campaign_id}/eligible-users",
+            get(campaign_eligible_users),
+        )
         .route("/tasks/complete", post(complete_task))
         .route("/tool/{service}/run", post(run_tool))
         .route("/proxy/{service}/run", post(run_proxy))
@@ -38,30 +85,66 @@ fn build_app(state: SharedState) -> Router {
             "/sponsored-apis",
             post(create_sponsored_api).get(list_sponsored_apis),
         )
+        .route(
+            "/sponsored-apis/create-price",
+            get(sponsored_api_create_price),
+        )
         .route("/sponsored-apis/{api_id}", get(get_sponsored_api))
         .route("/sponsored-apis/{api_id}/run", post(run_sponsored_api))
+        .route(
+            "/sponsored-apis/{api_id}/calls",
+            get(list_sponsored_api_calls),
+        )
         .route(
             "/webhooks/x402scan/settlement",
             post(ingest_x402scan_settlement),
         )
+        .route(
+            "/webhooks/x402scan/settlement:batch",
+            post(ingest_x402scan_settlement_batch),
+        )
         .route("/dashboard/sponsor/{campaign_id}", get(sponsor_dashboard))
         .route("/creator/metrics/event", post(record_creator_metric_event))
         .route("/creator/metrics", get(creator_metrics))
-        .route("/metrics", get(prometheus_metrics))
-        .layer(cors_layer_from_env())
-        .with_state(state)
+        .route("/admin/stats", get(admin_stats))
+        .route("/admin/top-sponsors", get(admin_top_sponsors))
+        .route("/admin/proxy-decisions", get(admin_proxy_decisions))
+        .route("/admin/reconcile", post(admin_reconcile))
+        .route("/admin/audit", get(admin_audit_log))
+        .route("/admin/config", get(admin_config))
+}
+
+/// Answers `504` once an API request runs past `REQUEST_TIMEOUT_SECS`, dropping the handler.
+/// Streamed bodies are not cut off once their headers have been sent.
+fn request_timeout_layer(timeout: Duration) -> TimeoutLayer {
+    TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, timeout)
 }
 
 fn cors_layer_from_env() -> CorsLayer {
+    let mut exposed = vec![
+        header::LOCATION,
+        header::ETAG,
+        HeaderName::from_static(PAYMENT_MODE_HEADER),
+        HeaderName::from_static(PAYMENT_REQUIRED_HEADER),
+        HeaderName::from_static(X402_VERSION_HEADER),
+        HeaderName::from_static(SKIPPED_ROWS_HEADER),
+        HeaderName::from_static(TOTAL_COUNT_HEADER),
+    ];
+    exposed.extend(read_payment_response_headers());
     let layer = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::HEAD, Method::POST, Method::OPTIONS])
         .allow_headers([
             header::CONTENT_TYPE,
             header::ACCEPT,
             header::AUTHORIZATION,
             HeaderName::from_static(PAYMENT_SIGNATURE_HEADER),
             HeaderName::from_static(X402_VERSION_HEADER),
-        ]);
+            HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            header::IF_MODIFIED_SINCE,
+            header::IF_NONE_MATCH,
+        ])
+        .expose_headers(exposed);
 
     let configured = std::env::var("CORS_ALLOW_ORIGINS").unwrap_or_else(|_| "*".to_string());
     if configured.trim() == "*" {
@@ -93,8 +176,16 @@ async fn main() {
         .compact()
         .init();
 
+    let app_state = AppState::new();
+    if app_state.config.x402_test_mode {
+        warn!(
+            "X402_TEST_MODE is ON: any non-empty PAYMENT-SIGNATURE is accepted as paid and the \
+             facilitator is never called; do not use this for real traffic"
+        );
+    }
+    let request_timeout = Duration::from_secs(app_state.config.request_timeout_secs);
     let state = SharedState {
-        inner: Arc::new(RwLock::new(AppState::new())),
+        inner: Arc::new(RwLock::new(app_state)),
     };
 
     if let Some(db) = {
@@ -106,12 +197,65 @@ async fn main() {
             .await
             .expect("database migrations should run");
 
-        if let Err(err) = load_campaigns_from_db(&state).await {
+        if let Err(err) = load_campaigns_from_db(&state, "loading campaigns").await {
             eprintln!("failed to load campaigns from database: {err}");
         }
     }
 
-    let app = build_app(state);
+    {
+        let mut state = state.inner.write().await;
+        if state.config.x402_settle_mode == SettleMode::Async {
+            // The facilitator keeps verifying a signature until it settles, so only the pending
+            // row stops one payment from being served again while it waits in the queue.
+            let Some(db) = state.db.clone() else {
+                panic!(
+                    "X402_SETTLE_MODE=async requires DATABASE_URL to track pending payments; \
+                     set it or use X402_SETTLE_MODE=sync"
+                );
+            };
+            info!("x402 settlement runs on the background worker (X402_SETTLE_MODE=async)");
+            state.settle_queue = Some(spawn_settlement_worker(
+                state.facilitator_http.clone(),
+                state.config.clone(),
+                Some(db.clone()),
+                state.metrics.clone(),
+            ));
+            spawn_settlement_reconciler(
+                state.facilitator_http.clone(),
+                state.config.clone(),
+                db,
+                state.metrics.clone(),
+            );
+        }
+    }
+
+    let metrics_port = std::env::var("METRICS_PORT").ok().map(|value| {
+        value
+            .trim()
+            .parse::<u16>()
+            .unwrap_or_else(|_| panic!("METRICS_PORT '{value}' is not a valid port"))
+    });
+
+    let app = match metrics_port {
+        Some(metrics_port) => {
+            let (app, ops) = build_split_apps(state, request_timeout);
+            let metrics_address = SocketAddr::from(([0, 0, 0, 0], metrics_port));
+            info!(
+                "payloadexchange-mvp metrics listening on http://{}",
+                metrics_address
+            );
+            let metrics_listener = tokio::net::TcpListener::bind(metrics_address)
+                .await
+                .expect("metrics bind should succeed");
+            tokio::spawn(async move {
+                if let Err(err) = axum::serve(metrics_listener, ops).await {
+                    eprintln!("metrics server error: {err}");
+                }
+            });
+            app
+        }
+        None => build_app(state, request_timeout),
+    };
 
     let port = std::env::var("PORT")
         .ok()
@@ -130,14 +274,39 @@ async fn main() {
 }
 
 async fn health(State(state): State<SharedState>) -> Response {
-    let state = state.inner.read().await;
+    let (metrics, db, http, config, cached_facilitator) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.facilitator_http.clone(),
+            state.config.clone(),
+            state.cached_facilitator_health(),
+        )
+    };
+
+    let database = check_database(db.as_ref()).await;
+
+    // Readiness is informational only; liveness stays 200 even when a dependency is down.
+    let facilitator = match cached_facilitator {
+        Some(status) => status,
+        None => {
+            let status = probe_facilitator(&http, &config).await;
+            let mut state = state.inner.write().await;
+            state.facilitator_health = Some((Instant::now(), status.clone()));
+            status
+        }
+    };
+
     respond(
-        &state.metrics,
+        &metrics,
         "/health",
         Ok((
             StatusCode::OK,
-            Json(MessageResponse {
+            Json(HealthResponse {
                 message: "ok".to_string(),
+                database,
+                facilitator,
             }),
         )),
     )
@@ -152,183 +321,382 @@ async fn create_profile(
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<UserProfile>)> = async {
-        let db = {
+    let result: ApiResult<Response> = async {
+        let (db, config) = {
             let state = state.inner.read().await;
-            state.db.clone()
-        }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+            (state.db.clone(), state.config.clone())
+        };
+        let db = db.ok_or_else(|| ApiError::database_required("creating a profile"))?;
+
+        let profile = new_user_profile(&config, payload)?;
+        let inserted = insert_user_profile(&db, profile)
+            .await?
+            .ok_or_else(|| ApiError::conflict("a profile with this email already exists"))?;
+
+        Ok(created_response(
+            &format!("/users/{}", inserted.id),
+            inserted,
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/profiles", result)
+}
+
+async fn create_profiles_batch(
+    State(state): State<SharedState>,
+    Json(payload): Json<Vec<serde_json::Value>>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
 
-        if payload.email.trim().is_empty() {
-            return Err(ApiError::validation("email is required"));
+    let result: ApiResult<(StatusCode, Json<Vec<ProfileBatchResult>>)> = async {
+        if payload.len() > MAX_PROFILE_BATCH {
+            return Err(ApiError::validation(format!(
+                "at most {MAX_PROFILE_BATCH} profiles can be created at once"
+            )));
         }
+        let (db, config) = {
+            let state = state.inner.read().await;
+            (state.db.clone(), state.config.clone())
+        };
+        let db = db.ok_or_else(|| ApiError::database_required("creating profiles"))?;
 
-        if payload.region.trim().is_empty() {
-            return Err(ApiError::validation("region is required"));
+        let mut results = Vec::with_capacity(payload.len());
+        for (index, row) in payload.into_iter().enumerate() {
+            results.push(create_batch_profile(&db, &config, index, row).await);
         }
 
-        let profile = UserProfile {
-            id: Uuid::new_v4(),
-            email: payload.email,
-            region: payload.region,
-            roles: payload.roles,
-            tools_used: payload.tools_used,
-            attributes: payload.attributes,
-            created_at: Utc::now(),
-        };
+        Ok((StatusCode::OK, Json(results)))
+    }
+    .await;
+
+    respond(&metrics, "/users:batch", result)
+}
+
+async fn list_profiles(State(state): State<SharedState>) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+
+    let result: ApiResult<Response> = async {
+        let db = {
+            let state = state.inner.read().await;
+            state.db.clone()
+        }
+        .ok_or_else(|| ApiError::database_required("listing profiles"))?;
 
-        let inserted = sqlx::query_as::<_, UserProfile>(
+        let total = sqlx::query_scalar::<_, i64>("select count(*) from users")
+            .fetch_one(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?;
+        let profiles = sqlx::query_as::<_, UserProfile>(
             r#"
-            insert into users (id, email, region, roles, tools_used, attributes, created_at)
-            values ($1, $2, $3, $4, $5, $6, $7)
-            returning id, email, region, roles, tools_used, attributes, created_at
+            select id, email, region, roles, tools_used, attributes, created_at
+            from users
+            order by created_at desc
             "#,
         )
-        .bind(profile.id)
-        .bind(profile.email)
-        .bind(profile.region)
-        .bind(profile.roles)
-        .bind(profile.tools_used)
-        .bind(DbJson(profile.attributes))
-        .bind(profile.created_at)
-        .fetch_one(&db)
+        .fetch_all(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
 
-        Ok((StatusCode::CREATED, Json(inserted)))
+        Ok(counted_list_response(profiles, 0, total))
     }
     .await;
 
     respond(&metrics, "/profiles", result)
 }
 
-async fn list_profiles(State(state): State<SharedState>) -> Response {
+async fn get_user(State(state): State<SharedState>, Path(user_id): Path<Uuid>) -> Response {
     let metrics = {
         let state = state.inner.read().await;
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<Vec<UserProfile>>)> = async {
+    let result: ApiResult<(StatusCode, Json<UserProfile>)> = async {
         let db = {
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("fetching a user"))?;
 
-        let profiles = sqlx::query_as::<_, UserProfile>(
+        let profile = sqlx::query_as::<_, UserProfile>(
             r#"
             select id, email, region, roles, tools_used, attributes, created_at
             from users
-            order by created_at desc
+            where id = $1
             "#,
         )
-        .fetch_all(&db)
+        .bind(user_id)
+        .fetch_optional(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?
+        .ok_or_else(|| ApiError::not_found("user not found"))?;
 
-        Ok((StatusCode::OK, Json(profiles)))
+        Ok((StatusCode::OK, Json(profile)))
     }
     .await;
 
-    respond(&metrics, "/profiles", result)
+    respond(&metrics, "/users/:user_id", result)
 }
 
-async fn register_user(
+async fn user_spend(
     State(state): State<SharedState>,
-    Json(payload): Json<CreateUserRequest>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<SinceQuery>,
 ) -> Response {
     let metrics = {
         let state = state.inner.read().await;
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<UserProfile>)> = async {
+    let result: ApiResult<(StatusCode, Json<UserSpendSummary>)> = async {
         let db = {
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("reporting user spend"))?;
 
-        if payload.email.trim().is_empty() {
-            return Err(ApiError::validation("email is required"));
-        }
+        let user_exists =
+            sqlx::query_scalar::<_, bool>("select exists(select 1 from users where id = $1)")
+                .bind(user_id)
+                .fetch_one(&db)
+                .await
+                .map_err(ApiError::from_sqlx)?;
 
-        if payload.region.trim().is_empty() {
-            return Err(ApiError::validation("region is required"));
+        if !user_exists {
+            return Err(ApiError::not_found("user not found"));
         }
 
-        let profile = UserProfile {
-            id: Uuid::new_v4(),
-            email: payload.email,
-            region: payload.region,
-            roles: payload.roles,
-            tools_used: payload.tools_used,
-            attributes: payload.attributes,
-            created_at: Utc::now(),
-        };
-
-        let inserted = sqlx::query_as::<_, UserProfile>(
+        // Proxy and tool runs land in payments; sponsored API runs are attributed by caller.
+        let row = sqlx::query_as::<_, UserSpendRow>(
             r#"
-            insert into users (id, email, region, roles, tools_used, attributes, created_at)
-            values ($1, $2, $3, $4, $5, $6, $7)
-            returning id, email, region, roles, tools_used, attributes, created_at
+            with user_payments as (
+                select source, amount_cents
+                from payments
+                where user_id = $1
+                  and status = 'settled'
+                  and ($2::timestamptz is null or created_at >= $2)
+            ),
+            user_api_calls as (
+                select payment_mode, amount_cents
+                from sponsored_api_calls
+                where caller = $1::text
+                  and ($2::timestamptz is null or created_at >= $2)
+            )
+            select
+                (coalesce((select sum(amount_cents) from user_payments where source = 'user'), 0)
+                    + coalesce((select sum(amount_cents) from user_api_calls
+                        where payment_mode = 'user_direct'), 0))::bigint as user_direct_spend_cents,
+                (coalesce((select sum(amount_cents) from user_payments where source = 'sponsor'), 0)
+                    + coalesce((select sum(amount_cents) from user_api_calls
+                        where payment_mode = 'sponsored'), 0))::bigint as sponsored_spend_cents,
+                ((select count(*) from user_payments)
+                    + (select count(*) from user_api_calls))::bigint as call_count
             "#,
         )
-        .bind(profile.id)
-        .bind(profile.email)
-        .bind(profile.region)
-        .bind(profile.roles)
-        .bind(profile.tools_used)
-        .bind(DbJson(profile.attributes))
-        .bind(profile.created_at)
+        .bind(user_id)
+        .bind(query.since)
         .fetch_one(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
+
+        let non_negative = |field: &str, value: i64| {
+            u64::try_from(value).map_err(|_| {
+                ApiError::database(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("{field} must be non-negative"),
+                )
+            })
+        };
+
+        Ok((
+            StatusCode::OK,
+            Json(UserSpendSummary {
+                user_id,
+                user_direct_spend_cents: non_negative(
+                    "user_direct_spend_cents",
+                    row.user_direct_spend_cents,
+                )?,
+                sponsored_spend_cents: non_negative(
+                    "sponsored_spend_cents",
+                    row.sponsored_spend_cents,
+                )?,
+                call_count: non_negative("call_count", row.call_count)?,
+                since: query.since,
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/users/:user_id/spend", result)
+}
+
+async fn register_user(
+    State(state): State<SharedState>,
+    Json(payload): Json<CreateUserRequest>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+
+    let result: ApiResult<Response> = async {
+        let (db, config) = {
+            let state = state.inner.read().await;
+            (state.db.clone(), state.config.clone())
+        };
+        let db = db.ok_or_else(|| ApiError::database_required("registering a user"))?;
+
+        let profile = new_user_profile(&config, payload)?;
+        let inserted = insert_user_profile(&db, profile)
+            .await?
+            .ok_or_else(|| ApiError::conflict("a profile with this email already exists"))?;
 
-        Ok((StatusCode::CREATED, Json(inserted)))
+        Ok(created_response(
+            &format!("/users/{}", inserted.id),
+            inserted,
+        ))
     }
     .await;
 
     respond(&metrics, "/register", result)
 }
 
+async fn lookup_user(
+    State(state): State<SharedState>,
+    Json(payload): Json<LookupUserRequest>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+
+    let result: ApiResult<Response> = async {
+        let (db, config) = {
+            let state = state.inner.read().await;
+            (state.db.clone(), state.config.clone())
+        };
+        let db = db.ok_or_else(|| ApiError::database_required("looking up a user"))?;
+
+        let email = normalize_email(&payload.email)?;
+        if let Some(existing) = find_user_by_email(&db, &email).await? {
+            return Ok((StatusCode::OK, Json(existing)).into_response());
+        }
+
+        let profile = new_user_profile(
+            &config,
+            CreateUserRequest {
+                email: email.clone(),
+                region: payload.region.unwrap_or_default(),
+                roles: payload.roles,
+                tools_used: payload.tools_used,
+                attributes: payload.attributes,
+            },
+        )?;
+
+        match insert_user_profile(&db, profile).await? {
+            Some(inserted) => Ok(created_response(
+                &format!("/users/{}", inserted.id),
+                inserted,
+            )),
+            // A concurrent lookup created it first.
+            None => find_user_by_email(&db, &email)
+                .await?
+                .map(|existing| (StatusCode::OK, Json(existing)).into_response())
+                .ok_or_else(|| ApiError::internal("user vanished after email conflict")),
+        }
+    }
+    .await;
+
+    respond(&metrics, "/users/lookup", result)
+}
+
 async fn create_campaign(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Json(payload): Json<CreateCampaignRequest>,
 ) -> Response {
-    let (metrics, db, public_base_url) = {
+    let (metrics, db, config) = {
         let state = state.inner.read().await;
         (
             state.metrics.clone(),
             state.db.clone(),
-            state.config.public_base_url.clone(),
+            state.config.clone(),
         )
     };
 
-    let result: ApiResult<(StatusCode, Json<CreateCampaignResponse>)> = async {
-        let db = db.ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+    let result: ApiResult<Response> = async {
+        let db = db.ok_or_else(|| ApiError::database_required("creating a campaign"))?;
 
         if payload.name.trim().is_empty() {
-            return Err(ApiError::validation("name is required"));
+            return Err(ApiError::validation_field("name", "name is required"));
         }
         if payload.sponsor.trim().is_empty() {
-            return Err(ApiError::validation("sponsor is required"));
+            return Err(ApiError::validation_field("sponsor", "sponsor is required"));
         }
         if payload.required_task.trim().is_empty() {
-            return Err(ApiError::validation("required_task is required"));
-        }
-        if payload.subsidy_per_call_cents == 0 {
-            return Err(ApiError::validation(
-                "subsidy_per_call_cents must be greater than 0",
+            return Err(ApiError::validation_field(
+                "required_task",
+                "required_task is required",
             ));
         }
+        validate_price_cents(
+            &config,
+            "subsidy_per_call_cents",
+            payload.subsidy_per_call_cents,
+        )?;
         if payload.budget_cents == 0 {
-            return Err(ApiError::validation("budget_cents must be greater than 0"));
+            return Err(ApiError::validation_field(
+                "budget_cents",
+                "budget_cents must be greater than 0",
+            ));
         }
+        let budget_cents = cents_to_i64("budget_cents", payload.budget_cents)?;
 
         for url in &payload.query_urls {
-            reqwest::Url::parse(url)
-                .map_err(|_| ApiError::validation(format!("invalid query URL: {url}")))?;
+            reqwest::Url::parse(url).map_err(|_| {
+                ApiError::validation_field("query_urls", format!("invalid query URL: {url}"))
+            })?;
+        }
+
+        let task_valid_for_secs = payload
+            .task_valid_for_secs
+            .map(|secs| {
+                i64::try_from(secs)
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| {
+                        ApiError::validation_field(
+                            "task_valid_for_secs",
+                            format!("task_valid_for_secs must be between 1 and {}", i64::MAX),
+                        )
+                    })
+            })
+            .transpose()?;
+
+        let budget_exhausted_webhook_url = validate_webhook_url(
+            "budget_exhausted_webhook_url",
+            payload.budget_exhausted_webhook_url.as_deref(),
+        )
+        .await?;
+
+        let idempotency_key = idempotency_key_from_headers(&headers)?;
+        let request_body = serde_json::to_value(&payload)
+            .map_err(|err| ApiError::internal(format!("failed to encode request: {err}")))?;
+
+        if let Some(key) = idempotency_key.as_deref()
+            && let Some(campaign) = replay_campaign_idempotency_key(&db, key, &request_body).await?
+        {
+            return Ok((
+                StatusCode::OK,
+                Json(campaign_created_response(&config.public_base_url, campaign)),
+            )
+                .into_response());
         }
 
         let candidate = Campaign {
@@ -342,95 +710,468 @@ async fn create_campaign(
             budget_total_cents: payload.budget_cents,
             budget_remaining_cents: payload.budget_cents,
             query_urls: payload.query_urls,
+            task_valid_for_secs: payload.task_valid_for_secs,
             active: true,
+            created_by: payload
+                .created_by
+                .map(|created_by| created_by.trim().to_string())
+                .filter(|created_by| !created_by.is_empty()),
             created_at: Utc::now(),
+            last_used_at: None,
+            budget_exhausted_webhook_url,
+            estimated_calls_remaining: Campaign::estimate_calls(
+                payload.budget_cents,
+                payload.subsidy_per_call_cents,
+            ),
+            estimated_total_calls: Campaign::estimate_calls(
+                payload.budget_cents,
+                payload.subsidy_per_call_cents,
+            ),
+            utilization: 0.0,
+            incomplete_task_behavior: payload
+                .incomplete_task_behavior
+                .unwrap_or(config.incomplete_task_behavior),
         };
 
-        let row = sqlx::query_as::<_, CampaignRow>(
-            r#"
-            insert into campaigns (
-                id, name, sponsor, target_roles, target_tools, required_task,
-                subsidy_per_call_cents, budget_total_cents, budget_remaining_cents,
-                query_urls, active, created_at
-            ) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-            returning id, name, sponsor, target_roles, target_tools, required_task,
-                subsidy_per_call_cents, budget_total_cents, budget_remaining_cents,
-                query_urls, active, created_at
-            "#,
-        )
+        let mut tx = db.begin().await.map_err(ApiError::from_sqlx)?;
+
+        let row = sqlx::query_as::<_, CampaignRow>(&format!(
+            "insert into campaigns ({CAMPAIGN_COLUMNS}) \
+             values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17) \
+             returning {CAMPAIGN_COLUMNS}"
+        ))
         .bind(candidate.id)
         .bind(candidate.name)
         .bind(candidate.sponsor)
         .bind(candidate.target_roles)
         .bind(candidate.target_tools)
         .bind(candidate.required_task)
-        .bind(candidate.subsidy_per_call_cents as i64)
-        .bind(candidate.budget_total_cents as i64)
-        .bind(candidate.budget_remaining_cents as i64)
+        .bind(cents_to_i64(
+            "subsidy_per_call_cents",
+            candidate.subsidy_per_call_cents,
+        )?)
+        .bind(budget_cents)
+        .bind(budget_cents)
         .bind(candidate.query_urls)
+        .bind(task_valid_for_secs)
         .bind(candidate.active)
+        .bind(candidate.created_by)
         .bind(candidate.created_at)
-        .fetch_one(&db)
+        .bind(candidate.last_used_at)
+        .bind(candidate.budget_exhausted_webhook_url)
+        .bind(candidate.incomplete_task_behavior.as_db_str())
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
 
         let campaign = Campaign::try_from(row)
             .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
 
-        let base = public_base_url.trim_end_matches('/');
-        let response = CreateCampaignResponse {
-            campaign: campaign.clone(),
-            campaign_url: format!("{base}/campaigns/{}", campaign.id),
-            dashboard_url: format!("{base}/dashboard/sponsor/{}", campaign.id),
-        };
+        record_audit(
+            &mut tx,
+            &AuditEntry {
+                id: Uuid::new_v4(),
+                actor: campaign.created_by.clone(),
+                action: "create".to_string(),
+                entity_type: "campaign".to_string(),
+                entity_id: campaign.id,
+                before: None,
+                after: serde_json::to_value(&campaign).ok(),
+                created_at: Utc::now(),
+            },
+        )
+        .await?;
+
+        if let Some(key) = idempotency_key.as_deref() {
+            let stored = sqlx::query(
+                r#"
+                insert into campaign_idempotency_keys (
+                    idempotency_key, campaign_id, request, created_at
+                )
+                values ($1, $2, $3, $4)
+                on conflict (idempotency_key) do nothing
+                "#,
+            )
+            .bind(key)
+            .bind(campaign.id)
+            .bind(DbJson(&request_body))
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await
+            .map_err(ApiError::from_sqlx)?;
+
+            // A concurrent request with the same key won the race; drop ours and replay theirs.
+            if stored.rows_affected() == 0 {
+                tx.rollback().await.map_err(ApiError::from_sqlx)?;
+                let campaign = replay_campaign_idempotency_key(&db, key, &request_body)
+                    .await?
+                    .ok_or_else(|| ApiError::internal("idempotency key vanished during replay"))?;
+                return Ok((
+                    StatusCode::OK,
+                    Json(campaign_created_response(&config.public_base_url, campaign)),
+                )
+                    .into_response());
+            }
+        }
 
-        Ok((StatusCode::CREATED, Json(response)))
+        tx.commit().await.map_err(ApiError::from_sqlx)?;
+
+        let response = campaign_created_response(&config.public_base_url, campaign);
+        Ok(created_response(
+            &format!("/campaigns/{}", response.campaign.id),
+            response,
+        ))
     }
     .await;
 
     respond(&metrics, "/campaigns", result)
 }
 
-async fn list_campaigns(State(state): State<SharedState>) -> Response {
-    let metrics = {
-        let state = state.inner.read().await;
-        state.metrics.clone()
+fn campaign_created_response(public_base_url: &str, campaign: Campaign) -> CreateCampaignResponse {
+    let base = public_base_url.trim_end_matches('/');
+    CreateCampaignResponse {
+        campaign_url: format!("{base}/campaigns/{}", campaign.id),
+        dashboard_url: format!("{base}/dashboard/sponsor/{}", campaign.id),
+        warnings: campaign_warnings(&campaign),
+        campaign,
+    }
+}
+
+fn campaign_warnings(campaign: &Campaign) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    // A remainder smaller than one subsidy can never be spent once the campaign deactivates.
+    if let Some(remainder) = campaign
+        .budget_total_cents
+        .checked_rem(campaign.subsidy_per_call_cents)
+        && remainder > 0
+    {
+        warnings.push(format!(
+            "budget_cents {} is not a multiple of subsidy_per_call_cents {}; {} cents will stay unspent",
+            campaign.budget_total_cents, campaign.subsidy_per_call_cents, remainder
+        ));
+    }
+
+    warnings
+}
+
+async fn replay_campaign_idempotency_key(
+    db: &sqlx::PgPool,
+    key: &str,
+    request_body: &serde_json::Value,
+) -> ApiResult<Option<Campaign>> {
+    #[derive(sqlx::FromRow)]
+    struct IdempotencyRow {
+        campaign_id: Uuid,
+        request: DbJson<serde_json::Value>,
+    }
+
+    let Some(stored) = sqlx::query_as::<_, IdempotencyRow>(
+        r#"
+        select campaign_id, request
+        from campaign_idempotency_keys
+        where idempotency_key = $1
+        "#,
+    )
+    .bind(key)
+    .fetch_optional(db)
+    .await
+    .map_err(ApiError::from_sqlx)?
+    else {
+        return Ok(None);
     };
 
-    let result: ApiResult<(StatusCode, Json<Vec<Campaign>>)> = async {
-        let mut campaigns = load_campaigns_from_db(&state).await?;
-        campaigns.sort_by_key(|campaign| campaign.created_at);
-        Ok((StatusCode::OK, Json(campaigns)))
+    if &stored.request.0 != request_body {
+        return Err(ApiError::conflict(
+            "Idempotency-Key was already used with a different request body",
+        ));
     }
-    .await;
 
-    respond(&metrics, "/campaigns", result)
+    let row = sqlx::query_as::<_, CampaignRow>(&campaign_select("where id = $1"))
+        .bind(stored.campaign_id)
+        .fetch_one(db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+
+    Campaign::try_from(row)
+        .map(Some)
+        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
 }
 
-async fn get_campaign(State(state): State<SharedState>, Path(campaign_id): Path<Uuid>) -> Response {
+async fn list_campaigns(
+    State(state): State<SharedState>,
+    Query(query): Query<ListOrderQuery>,
+) -> Response {
     let metrics = {
         let state = state.inner.read().await;
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<Campaign>)> = async {
-        let campaigns = load_campaigns_from_db(&state).await?;
-        let campaign = campaigns
-            .into_iter()
-            .find(|campaign| campaign.id == campaign_id)
-            .ok_or_else(|| ApiError::not_found("campaign not found"))?;
-        Ok((StatusCode::OK, Json(campaign)))
+    let result: ApiResult<Response> = async {
+        let db = {
+            let state = state.inner.read().await;
+            state.db.clone()
+        }
+        .ok_or_else(|| ApiError::database_required("listing campaigns"))?;
+        let total = sqlx::query_scalar::<_, i64>("select count(*) from campaigns")
+            .fetch_one(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?;
+
+        let (mut campaigns, skipped) = load_campaigns_from_db(&state, "listing campaigns").await?;
+        match query.order {
+            ListOrder::Created => campaigns.sort_by_key(|campaign| campaign.created_at),
+            ListOrder::LastUsed => campaigns.sort_by_key(|campaign| {
+                (
+                    campaign.last_used_at.is_none(),
+                    std::cmp::Reverse(campaign.last_used_at),
+                )
+            }),
+        }
+        Ok(counted_list_response(campaigns, skipped, total))
     }
     .await;
 
-    respond(&metrics, "/campaigns/:campaign_id", result)
+    respond(&metrics, "/campaigns", result)
 }
 
-async fn list_campaign_discovery(State(state): State<SharedState>) -> Response {
-    let (metrics, base) = {
-        let state = state.inner.read().await;
-        (
-            state.metrics.clone(),
+async fn get_campaign(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(campaign_id): Path<Uuid>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+
+    let result: ApiResult<Response> = async {
+        let db = {
+            let state = state.inner.read().await;
+            state.db.clone()
+        }
+        .ok_or_else(|| ApiError::database_required("fetching a campaign"))?;
+
+        // Unlike the list, a corrupt row here is an error rather than a silent 404.
+        let row = sqlx::query_as::<_, UpdatedRow<CampaignRow>>(&format!(
+            "select {CAMPAIGN_COLUMNS}, updated_at from campaigns where id = $1"
+        ))
+        .bind(campaign_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .ok_or_else(|| ApiError::not_found("campaign not found"))?;
+        let campaign = Campaign::try_from(row.row)
+            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+        Ok(conditional_response(&headers, row.updated_at, campaign))
+    }
+    .await;
+
+    respond(&metrics, "/campaigns/:campaign_id", result)
+}
+
+async fn pause_campaign(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(campaign_id): Path<Uuid>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+    let result = set_campaign_active(&state, &headers, campaign_id, false).await;
+    respond(&metrics, "/campaigns/:campaign_id/pause", result)
+}
+
+async fn resume_campaign(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(campaign_id): Path<Uuid>,
+) -> Response {
+    let metrics = {
+        let state = state.inner.read().await;
+        state.metrics.clone()
+    };
+    let result = set_campaign_active(&state, &headers, campaign_id, true).await;
+    respond(&metrics, "/campaigns/:campaign_id/resume", result)
+}
+
+/// Shared body of pause/resume. Toggling to the current state is a no-op and writes no audit row.
+async fn set_campaign_active(
+    state: &SharedState,
+    headers: &HeaderMap,
+    campaign_id: Uuid,
+    active: bool,
+) -> ApiResult<(StatusCode, Json<Campaign>)> {
+    let (db, config) = {
+        let state = state.inner.read().await;
+        (state.db.clone(), state.config.clone())
+    };
+    require_management_key(&config, headers)?;
+    let db = db.ok_or_else(|| ApiError::database_required("pausing or resuming a campaign"))?;
+
+    let mut tx = db.begin().await.map_err(ApiError::from_sqlx)?;
+    let before = sqlx::query_as::<_, CampaignRow>(&campaign_select("where id = $1 for update"))
+        .bind(campaign_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .ok_or_else(|| ApiError::not_found("campaign not found"))
+        .and_then(|row| {
+            Campaign::try_from(row)
+                .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
+        })?;
+    if before.active == active {
+        return Ok((StatusCode::OK, Json(before)));
+    }
+
+    // Mirrors the proxy's auto-deactivation: a campaign that cannot fund the cheapest
+    // allowed call would be switched off again by the first request it matched.
+    if active && before.budget_remaining_cents < config.min_price_cents {
+        return Err(ApiError::precondition(format!(
+            "campaign has {} cents remaining; at least {} cents are needed to sponsor a call",
+            before.budget_remaining_cents, config.min_price_cents
+        )));
+    }
+
+    let row = sqlx::query_as::<_, CampaignRow>(&format!(
+        "update campaigns set active = $2 where id = $1 returning {CAMPAIGN_COLUMNS}"
+    ))
+    .bind(campaign_id)
+    .bind(active)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+    let campaign = Campaign::try_from(row)
+        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+    record_audit(
+        &mut tx,
+        &AuditEntry {
+            id: Uuid::new_v4(),
+            actor: None,
+            action: if active { "resume" } else { "pause" }.to_string(),
+            entity_type: "campaign".to_string(),
+            entity_id: campaign.id,
+            before: serde_json::to_value(&before).ok(),
+            after: serde_json::to_value(&campaign).ok(),
+            created_at: Utc::now(),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(ApiError::from_sqlx)?;
+
+    Ok((StatusCode::OK, Json(campaign)))
+}
+
+async fn list_campaign_completions(
+    State(state): State<SharedState>,
+    Path(campaign_id): Path<Uuid>,
+    Query(query): Query<TaskCompletionsQuery>,
+) -> Response {
+    let (metrics, db) = {
+        let state = state.inner.read().await;
+        (state.metrics.clone(), state.db.clone())
+    };
+
+    let result: ApiResult<(StatusCode, Json<Vec<TaskCompletion>>)> = async {
+        let db = db.ok_or_else(|| ApiError::database_required("listing task completions"))?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_TASK_COMPLETIONS_LIMIT)
+            .clamp(1, MAX_TASK_COMPLETIONS_LIMIT);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let exists =
+            sqlx::query_scalar::<_, bool>("select exists(select 1 from campaigns where id = $1)")
+                .bind(campaign_id)
+                .fetch_one(&db)
+                .await
+                .map_err(ApiError::from_sqlx)?;
+        if !exists {
+            return Err(ApiError::not_found("campaign not found"));
+        }
+
+        let completions = sqlx::query_as::<_, TaskCompletion>(
+            r#"
+            select id, campaign_id, user_id, task_name, details, created_at
+            from task_completions
+            where campaign_id = $1
+              and ($2::text is null or task_name = $2)
+              and ($3::timestamptz is null or created_at >= $3)
+              and ($4::timestamptz is null or created_at < $4)
+            order by created_at desc, id
+            limit $5 offset $6
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(query.task_name.as_deref())
+        .bind(query.since)
+        .bind(query.until)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+
+        Ok((StatusCode::OK, Json(completions)))
+    }
+    .await;
+
+    respond(&metrics, "/campaigns/:campaign_id/completions", result)
+}
+
+async fn campaign_eligible_users(
+    State(state): State<SharedState>,
+    Path(campaign_id): Path<Uuid>,
+    Query(query): Query<EligibleUsersQuery>,
+) -> Response {
+    let (metrics, db) = {
+        let state = state.inner.read().await;
+        (state.metrics.clone(), state.db.clone())
+    };
+
+    let result: ApiResult<(StatusCode, Json<EligibleUsers>)> = async {
+        let db = db.ok_or_else(|| ApiError::database_required("counting eligible users"))?;
+
+        let sample_limit = query
+            .limit
+            .unwrap_or(DEFAULT_ELIGIBLE_USERS_SAMPLE)
+            .clamp(0, MAX_ELIGIBLE_USERS_SAMPLE);
+
+        let campaign = sqlx::query_as::<_, CampaignRow>(&campaign_select("where id = $1"))
+            .bind(campaign_id)
+            .fetch_optional(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?
+            .ok_or_else(|| ApiError::not_found("campaign not found"))
+            .and_then(|row| {
+                Campaign::try_from(row)
+                    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
+            })?;
+
+        let (eligible_users, sample) = load_eligible_users(&db, &campaign, sample_limit).await?;
+
+        Ok((
+            StatusCode::OK,
+            Json(EligibleUsers {
+                campaign_id,
+                eligible_users,
+                sample,
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/campaigns/:campaign_id/eligible-users", result)
+}
+
+async fn list_campaign_discovery(State(state): State<SharedState>) -> Response {
+    let (metrics, base) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
             state
                 .config
                 .public_base_url
@@ -439,8 +1180,9 @@ async fn list_campaign_discovery(State(state): State<SharedState>) -> Response {
         )
     };
 
-    let result: ApiResult<(StatusCode, Json<Vec<CampaignDiscoveryItem>>)> = async {
-        let campaigns = load_campaigns_from_db(&state).await?;
+    let result: ApiResult<Response> = async {
+        let (campaigns, skipped) =
+            load_campaigns_from_db(&state, "listing campaign discovery").await?;
         let mut rows: Vec<CampaignDiscoveryItem> = campaigns
             .into_iter()
             .filter(|campaign| campaign.active)
@@ -456,40 +1198,33 @@ async fn list_campaign_discovery(State(state): State<SharedState>) -> Response {
             })
             .collect();
         rows.sort_by_key(|item| item.name.clone());
-        Ok((StatusCode::OK, Json(rows)))
+        Ok(list_response(rows, skipped))
     }
     .await;
 
     respond(&metrics, "/campaigns/discovery", result)
 }
 
-async fn load_campaigns_from_db(state: &SharedState) -> ApiResult<Vec<Campaign>> {
+/// Every readable campaign, newest first, and how many unreadable rows were skipped.
+/// `operation` names the caller in the error when Postgres is not configured.
+async fn load_campaigns_from_db(
+    state: &SharedState,
+    operation: &str,
+) -> ApiResult<(Vec<Campaign>, usize)> {
     let db = {
         let state = state.inner.read().await;
         state.db.clone()
     }
-    .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
-
-    let rows = sqlx::query_as::<_, CampaignRow>(
-        r#"
-        select id, name, sponsor, target_roles, target_tools, required_task,
-            subsidy_per_call_cents, budget_total_cents, budget_remaining_cents,
-            query_urls, active, created_at
-        from campaigns
-        order by created_at desc
-        "#,
-    )
-    .fetch_all(&db)
-    .await
-    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    .ok_or_else(|| ApiError::database_required(operation))?;
 
-    let campaigns: Vec<Campaign> = rows
-        .into_iter()
-        .map(Campaign::try_from)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    let rows = sqlx::query_as::<_, CampaignRow>(&campaign_select("order by created_at desc"))
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
 
-    Ok(campaigns)
+    Ok(convert_list_rows(rows, "campaign", |row: &CampaignRow| {
+        row.id
+    }))
 }
 
 async fn complete_task(
@@ -506,60 +1241,9 @@ async fn complete_task(
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
-
-        // Verify campaign exists
-        let campaign_exists =
-            sqlx::query_scalar::<_, bool>("select exists(select 1 from campaigns where id = $1)")
-                .bind(payload.campaign_id)
-                .fetch_one(&db)
-                .await
-                .map_err(|err| {
-                    ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
-                })?;
-
-        if !campaign_exists {
-            return Err(ApiError::not_found("campaign not found"));
-        }
-
-        // Verify user exists
-        let user_exists =
-            sqlx::query_scalar::<_, bool>("select exists(select 1 from users where id = $1)")
-                .bind(payload.user_id)
-                .fetch_one(&db)
-                .await
-                .map_err(|err| {
-                    ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
-                })?;
-
-        if !user_exists {
-            return Err(ApiError::not_found("user not found"));
-        }
-
-        let completion = TaskCompletion {
-            id: Uuid::new_v4(),
-            campaign_id: payload.campaign_id,
-            user_id: payload.user_id,
-            task_name: payload.task_name,
-            details: payload.details,
-            created_at: Utc::now(),
-        };
+        .ok_or_else(|| ApiError::database_required("recording a task completion"))?;
 
-        sqlx::query(
-            r#"
-            insert into task_completions (id, campaign_id, user_id, task_name, details, created_at)
-            values ($1, $2, $3, $4, $5, $6)
-            "#,
-        )
-        .bind(completion.id)
-        .bind(completion.campaign_id)
-        .bind(completion.user_id)
-        .bind(completion.task_name.clone())
-        .bind(completion.details.clone())
-        .bind(completion.created_at)
-        .execute(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        let completion = record_task_completion(&db, payload).await?;
 
         Ok((StatusCode::CREATED, Json(completion)))
     }
@@ -572,42 +1256,76 @@ async fn run_tool(
     State(state): State<SharedState>,
     Path(service): Path<String>,
     headers: HeaderMap,
-    Json(payload): Json<ServiceRunRequest>,
+    Json(mut payload): Json<ServiceRunRequest>,
 ) -> Response {
-    let (price, metrics, http, config) = {
+    let (db, price, metrics, facilitator_http, config, settle_queue) = {
         let state = state.inner.read().await;
         (
+            state.db.clone(),
             state.service_price(&service),
             state.metrics.clone(),
-            state.http.clone(),
+            state.facilitator_http.clone(),
             state.config.clone(),
+            state.settle_queue.clone(),
         )
     };
 
+    let attribution =
+        match ensure_public_service(&service).and_then(|_| payment_attribution(&payload)) {
+            Ok(attribution) => attribution,
+            Err(err) => {
+                return respond(
+                    &metrics,
+                    "/tool/:service/run",
+                    Err::<Response, ApiError>(err),
+                );
+            }
+        };
+    payload.caller = attribution.caller.clone();
+
+    let request_id = request_id_from_headers(&headers);
+    let signature = payment_signature(&headers, payload.payment.as_deref());
     let resource_path = format!("/tool/{service}/run");
     let result: ApiResult<Response> = match verify_x402_payment(
-        &http,
-        &config,
+        &PaymentContext {
+            http: &facilitator_http,
+            config: &config,
+            db: db.as_ref(),
+            metrics: &metrics,
+            settle_queue: settle_queue.as_ref(),
+        },
         &service,
         price,
         &resource_path,
-        &headers,
+        signature.as_deref(),
+        Some(attribution.clone()),
+        &request_id,
     )
     .await
     {
         Ok(payment) => {
-            metrics
-                .payment_events_total
-                .with_label_values(&["user_direct", "settled"])
-                .inc();
+            count_user_direct_payment(&metrics, &payment);
+
+            // The payment already settled on-chain, so a bookkeeping failure must not hide
+            // the paid result from the caller.
+            if let Some(db) = &db {
+                spawn_user_direct_payment_record(
+                    db.clone(),
+                    service.clone(),
+                    price,
+                    attribution,
+                    payment.clone(),
+                );
+            }
 
             Ok(build_paid_tool_response(
                 service,
                 payload,
-                "user_direct".to_string(),
+                PaymentMode::UserDirect,
                 None,
                 payment.tx_hash,
-                Some(payment.payment_response_header.as_str()),
+                payment.payment_response_header.as_deref(),
+                &config,
             ))
         }
         Err(err) => Err(err),
@@ -620,44 +1338,67 @@ async fn run_proxy(
     State(state): State<SharedState>,
     Path(service): Path<String>,
     headers: HeaderMap,
-    Json(payload): Json<ServiceRunRequest>,
+    Json(mut payload): Json<ServiceRunRequest>,
 ) -> Response {
-    let has_header = headers.contains_key(PAYMENT_SIGNATURE_HEADER);
+    let signature = payment_signature(&headers, payload.payment.as_deref());
 
-    let (db, price, metrics, http, config) = {
+    let (db, price, metrics, facilitator_http, config, settle_queue) = {
         let state = state.inner.read().await;
         (
             state.db.clone(),
             state.service_price(&service),
             state.metrics.clone(),
-            state.http.clone(),
+            state.facilitator_http.clone(),
             state.config.clone(),
+            state.settle_queue.clone(),
         )
     };
 
+    let (attribution, idempotency_key) = match ensure_public_service(&service)
+        .and_then(|_| payment_attribution(&payload))
+        .and_then(|attribution| Ok((attribution, idempotency_key_from_headers(&headers)?)))
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            return respond(
+                &metrics,
+                "/proxy/:service/run",
+                Err::<Response, ApiError>(err),
+            );
+        }
+    };
+    payload.caller = attribution.caller.clone();
+    // Sponsorship is matched against a profile, so proxy runs cannot be anonymous.
+    let Some(user_id) = attribution.user_id else {
+        return respond(
+            &metrics,
+            "/proxy/:service/run",
+            Err::<Response, ApiError>(ApiError::validation_field(
+                "user_id",
+                "user_id is required for proxy runs",
+            )),
+        );
+    };
+
     let db = match db {
         Some(db) => db,
         None => {
             return respond(
                 &metrics,
                 "/proxy/:service/run",
-                Err::<Response, ApiError>(ApiError::config(
-                    "Postgres not configured; set DATABASE_URL",
-                )),
+                Err::<Response, ApiError>(ApiError::database_required("sponsoring a proxy run")),
             );
         }
     };
 
-    if has_header {
+    if signature.is_some() {
         // Verify user exists in database
         let user_exists =
             sqlx::query_scalar::<_, bool>("select exists(select 1 from users where id = $1)")
-                .bind(payload.user_id)
+                .bind(user_id)
                 .fetch_one(&db)
                 .await
-                .map_err(|err| {
-                    ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
-                })
+                .map_err(ApiError::from_sqlx)
                 .unwrap_or(false);
 
         if !user_exists {
@@ -670,28 +1411,57 @@ async fn run_proxy(
             );
         }
 
+        let request_id = request_id_from_headers(&headers);
         let resource_path = format!("/proxy/{service}/run");
-        let result =
-            match verify_x402_payment(&http, &config, &service, price, &resource_path, &headers)
-                .await
-            {
-                Ok(payment) => {
-                    metrics
-                        .payment_events_total
-                        .with_label_values(&["user_direct", "settled"])
-                        .inc();
+        let result = match verify_x402_payment(
+            &PaymentContext {
+                http: &facilitator_http,
+                config: &config,
+                db: Some(&db),
+                metrics: &metrics,
+                settle_queue: settle_queue.as_ref(),
+            },
+            &service,
+            price,
+            &resource_path,
+            signature.as_deref(),
+            Some(attribution.clone()),
+            &request_id,
+        )
+        .await
+        {
+            Ok(payment) => {
+                count_user_direct_payment(&metrics, &payment);
+
+                spawn_user_direct_payment_record(
+                    db.clone(),
+                    service.clone(),
+                    price,
+                    attribution,
+                    payment.clone(),
+                );
+                spawn_proxy_decision_log(
+                    db.clone(),
+                    ProxyDecisionEvent {
+                        decision: ProxyDecision::UserPaid,
+                        user_id,
+                        service: service.clone(),
+                        campaign_id: None,
+                    },
+                );
 
-                    Ok(build_paid_tool_response(
-                        service,
-                        payload,
-                        "user_direct".to_string(),
-                        None,
-                        payment.tx_hash,
-                        Some(payment.payment_response_header.as_str()),
-                    ))
-                }
-                Err(err) => Err(err),
-            };
+                Ok(build_paid_tool_response(
+                    service,
+                    payload,
+                    PaymentMode::UserDirect,
+                    None,
+                    payment.tx_hash,
+                    payment.payment_response_header.as_deref(),
+                    &config,
+                ))
+            }
+            Err(err) => Err(err),
+        };
 
         return respond(&metrics, "/proxy/:service/run", result);
     }
@@ -700,10 +1470,10 @@ async fn run_proxy(
     let user = sqlx::query_as::<_, UserProfile>(
         "select id, email, region, roles, tools_used, attributes, created_at from users where id = $1"
     )
-    .bind(payload.user_id)
+    .bind(user_id)
     .fetch_optional(&db)
     .await
-    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+    .map_err(ApiError::from_sqlx)
     .and_then(|opt| {
         opt.ok_or_else(|| ApiError::not_found("user profile is required before proxy usage"))
     });
@@ -719,29 +1489,42 @@ async fn run_proxy(
         }
     };
 
-    // Load campaigns from database
-    let campaigns = sqlx::query_as::<_, CampaignRow>(
-        r#"
-        select id, name, sponsor, target_roles, target_tools, required_task,
-            subsidy_per_call_cents, budget_total_cents, budget_remaining_cents,
-            query_urls, active, created_at
-        from campaigns
-        where active = true and budget_remaining_cents >= $1
-        order by created_at desc
-        "#,
-    )
-    .bind(price as i64)
-    .fetch_all(&db)
-    .await
-    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
-    .and_then(|rows| {
-        rows.into_iter()
-            .map(Campaign::try_from)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
-    });
+    let tx_hash = sponsored_tx_hash(user_id, &service, idempotency_key.as_deref());
+    let request_hash = idempotency_key
+        .is_some()
+        .then(|| sponsored_request_hash(&payload.input));
+    // A keyed retry is answered from the first run before any campaign is chosen: the first
+    // run may have exhausted its campaign, and another must not be charged for the same call.
+    if idempotency_key.is_some() {
+        match find_sponsored_replay(&db, &tx_hash, request_hash.as_deref()).await {
+            Ok(Some(campaign)) => {
+                return respond(
+                    &metrics,
+                    "/proxy/:service/run",
+                    Ok(build_paid_tool_response(
+                        service,
+                        payload,
+                        PaymentMode::Sponsored,
+                        Some(&campaign),
+                        Some(tx_hash),
+                        None,
+                        &config,
+                    )),
+                );
+            }
+            Ok(None) => {}
+            Err(err) => {
+                return respond(
+                    &metrics,
+                    "/proxy/:service/run",
+                    Err::<Response, ApiError>(err),
+                );
+            }
+        }
+    }
 
-    let campaigns = match campaigns {
+    // Only campaigns targeting the user come back, so this stays small as campaigns grow.
+    let campaigns = match load_targeted_campaigns(&db, &user, price).await {
         Ok(campaigns) => campaigns,
         Err(err) => {
             return respond(
@@ -752,21 +1535,96 @@ async fn run_proxy(
         }
     };
 
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            return respond(
+                &metrics,
+                "/proxy/:service/run",
+                Err::<Response, ApiError>(ApiError::from_sqlx(err)),
+            );
+        }
+    };
+
+    // Recorded in the eligibility transaction so the check below sees it without racing a
+    // separate `/tasks/complete` call.
+    if let Some(request) = payload.complete_task.take() {
+        let recorded = async {
+            // `campaigns` only holds those targeting the user, so any other campaign is looked
+            // up on its own to reject it as untargeted rather than missing.
+            let untargeted = if campaigns
+                .iter()
+                .any(|campaign| campaign.id == request.campaign_id)
+            {
+                Vec::new()
+            } else {
+                sqlx::query_as::<_, CampaignRow>(&campaign_select(&format!(
+                    "where ({CAMPAIGN_FUNDED_FILTER} or {CAMPAIGN_EXHAUSTED_FILTER}) and id = $2"
+                )))
+                .bind(price as i64)
+                .bind(request.campaign_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(ApiError::from_sqlx)?
+                .map(Campaign::try_from)
+                .transpose()
+                .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?
+                .into_iter()
+                .collect()
+            };
+            let candidates = if untargeted.is_empty() {
+                &campaigns
+            } else {
+                &untargeted
+            };
+            validate_embedded_task(&user, &request, candidates)?;
+            record_task_completion(&mut *tx, request).await
+        }
+        .await;
+        if let Err(err) = recorded {
+            return respond(
+                &metrics,
+                "/proxy/:service/run",
+                Err::<Response, ApiError>(err),
+            );
+        }
+    }
+
     let mut match_without_task: Option<Campaign> = None;
-    let mut match_with_task: Option<Campaign> = None;
+    let mut match_with_task: Option<(Campaign, u64)> = None;
+    let mut eligibility = SponsorEligibility::default();
 
     for campaign in campaigns {
-        if !user_matches_campaign(&user, &campaign) {
+        // Already filtered in SQL; kept as a guard in case the two ever drift apart.
+        let funded = campaign.active && campaign.budget_remaining_cents >= price;
+        if !(funded && user_matches_campaign(&user, &campaign).matched) {
             continue;
         }
+        let Some(charge) = campaign.sponsor_charge(price) else {
+            eligibility.matched_subsidy_below_price += 1;
+            continue;
+        };
 
-        match has_completed_task(&db, campaign.id, payload.user_id, &campaign.required_task).await {
+        match has_completed_task(
+            &mut *tx,
+            campaign.id,
+            user_id,
+            &campaign.required_task,
+            campaign.task_valid_for_secs,
+        )
+        .await
+        {
             Ok(true) => {
-                match_with_task = Some(campaign);
+                match_with_task = Some((campaign, charge));
                 break;
             }
             Ok(false) => {
-                if match_without_task.is_none() {
+                eligibility.matched_incomplete_task += 1;
+                // Skipping campaigns still count above, so the 402 can hint that a task
+                // would have sponsored the call.
+                if match_without_task.is_none()
+                    && campaign.incomplete_task_behavior == IncompleteTaskBehavior::RequireTaskOrPay
+                {
                     match_without_task = Some(campaign);
                 }
             }
@@ -780,101 +1638,196 @@ async fn run_proxy(
         }
     }
 
-    if let Some(campaign) = match_with_task {
-        let new_remaining = campaign.budget_remaining_cents.saturating_sub(price);
-        let still_active = new_remaining >= price && new_remaining > 0;
-
-        // Update campaign budget in database
-        let budget_update = sqlx::query(
-            r#"
-            update campaigns
-            set budget_remaining_cents = $1, active = $2
-            where id = $3
-            "#,
-        )
-        .bind(new_remaining as i64)
-        .bind(still_active)
-        .bind(campaign.id)
-        .execute(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()));
-        if let Err(err) = budget_update {
-            return respond(
-                &metrics,
-                "/proxy/:service/run",
-                Err::<Response, ApiError>(err),
-            );
+    let Some((campaign, charge)) = match_with_task else {
+        // The targeting misses are only reported in the 402, so they are counted only here.
+        if match_without_task.is_none() {
+            match load_targeting_misses(&mut *tx, &user, price).await {
+                Ok(misses) => {
+                    eligibility.active_campaigns = misses.active_campaigns as u64;
+                    eligibility.unmatched_roles = misses.unmatched_roles as u64;
+                    eligibility.unmatched_tools = misses.unmatched_tools as u64;
+                    eligibility.matched_budget_exhausted = misses.matched_budget_exhausted as u64;
+                }
+                Err(err) => {
+                    return respond(
+                        &metrics,
+                        "/proxy/:service/run",
+                        Err::<Response, ApiError>(err),
+                    );
+                }
+            }
         }
+        spawn_proxy_decision_log(
+            db.clone(),
+            ProxyDecisionEvent {
+                decision: ProxyDecision::unsponsored(&eligibility),
+                user_id,
+                service: service.clone(),
+                campaign_id: match_without_task.as_ref().map(|campaign| campaign.id),
+            },
+        );
+        let err = match match_without_task {
+            Some(campaign) => task_required_error(&campaign),
+            None => payment_required_error(
+                &config,
+                &service,
+                price,
+                &format!("/proxy/{service}/run"),
+                "no eligible sponsor campaign found",
+                "either complete a sponsor task or pay with PAYMENT-SIGNATURE",
+            )
+            .with_details(serde_json::to_value(&eligibility).unwrap_or_default()),
+        };
+        // Nothing is debited, but an embedded task completion is still kept.
+        let result = tx
+            .commit()
+            .await
+            .map_err(ApiError::from_sqlx)
+            .and(Err::<Response, ApiError>(err));
+        return respond(&metrics, "/proxy/:service/run", result);
+    };
 
-        let tx_hash = format!("sponsor-{}", Uuid::new_v4());
+    let new_remaining = campaign.budget_remaining_cents.saturating_sub(charge);
+    let still_active = new_remaining >= charge && new_remaining > 0;
 
-        // Save payment to database
-        let payment_insert = sqlx::query(
-            r#"
-            insert into payments (tx_hash, campaign_id, service, amount_cents, payer, source, status, created_at)
-            values ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#,
+    // Written before the debit: a retry finds the first attempt's row and is answered as
+    // sponsored again without charging the campaign twice.
+    let payment_insert = sqlx::query(
+        r#"
+        insert into payments (
+            tx_hash, campaign_id, service, amount_cents, payer, source, status,
+            created_at, user_id, request_hash
         )
-        .bind(&tx_hash)
-        .bind(campaign.id)
-        .bind(&service)
-        .bind(price as i64)
-        .bind(&campaign.sponsor)
-        .bind("sponsor")
-        .bind("settled")
-        .bind(Utc::now())
-        .execute(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()));
-        if let Err(err) = payment_insert {
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        on conflict (tx_hash) do nothing
+        "#,
+    )
+    .bind(&tx_hash)
+    .bind(campaign.id)
+    .bind(&service)
+    .bind(charge as i64)
+    .bind(&campaign.sponsor)
+    .bind(PaymentSource::Sponsor.as_db_str())
+    .bind(PaymentStatus::Settled.as_db_str())
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(&request_hash)
+    .execute(&mut *tx)
+    .await
+    .map_err(ApiError::from_sqlx);
+    match payment_insert {
+        Ok(done) if done.rows_affected() == 0 => {
+            // Only a keyed run can collide, when a concurrent first attempt committed after the
+            // lookup above. Dropping the transaction discards this attempt's embedded task
+            // completion too; the first attempt already recorded it.
+            drop(tx);
+            let replay = find_sponsored_replay(&db, &tx_hash, request_hash.as_deref())
+                .await
+                .and_then(|campaign| {
+                    campaign.ok_or_else(|| ApiError::internal("sponsored payment vanished"))
+                })
+                .map(|campaign| {
+                    build_paid_tool_response(
+                        service,
+                        payload,
+                        PaymentMode::Sponsored,
+                        Some(&campaign),
+                        Some(tx_hash),
+                        None,
+                        &config,
+                    )
+                });
+            return respond(&metrics, "/proxy/:service/run", replay);
+        }
+        Ok(_) => {}
+        Err(err) => {
             return respond(
                 &metrics,
                 "/proxy/:service/run",
                 Err::<Response, ApiError>(err),
             );
         }
+    }
 
-        metrics
-            .payment_events_total
-            .with_label_values(&["sponsored", "settled"])
-            .inc();
-        metrics.sponsor_spend_cents_total.inc_by(price);
-
+    // Update campaign budget in database
+    let budget_update = sqlx::query(
+        r#"
+        update campaigns
+        set budget_remaining_cents = $1, active = $2, last_used_at = $3
+        where id = $4
+        "#,
+    )
+    .bind(new_remaining as i64)
+    .bind(still_active)
+    .bind(Utc::now())
+    .bind(campaign.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(ApiError::from_sqlx);
+    if let Err(err) = budget_update {
         return respond(
             &metrics,
             "/proxy/:service/run",
-            Ok(build_paid_tool_response(
-                service,
-                payload,
-                "sponsored".to_string(),
-                Some(campaign.sponsor),
-                Some(tx_hash),
-                None,
-            )),
+            Err::<Response, ApiError>(err),
         );
     }
 
-    if let Some(campaign) = match_without_task {
+    if let Err(err) = tx.commit().await {
         return respond(
             &metrics,
             "/proxy/:service/run",
-            Err::<Response, ApiError>(ApiError::precondition(format!(
-                "complete sponsor task '{}' for campaign '{}' before sponsored usage",
-                campaign.required_task, campaign.name
-            ))),
+            Err::<Response, ApiError>(ApiError::from_sqlx(err)),
+        );
+    }
+
+    spawn_proxy_decision_log(
+        db.clone(),
+        ProxyDecisionEvent {
+            decision: ProxyDecision::SponsoredPaid,
+            user_id,
+            service: service.clone(),
+            campaign_id: Some(campaign.id),
+        },
+    );
+    metrics
+        .payment_events_total
+        .with_label_values(&[
+            PaymentMode::Sponsored.as_str(),
+            PaymentStatus::Settled.as_db_str(),
+        ])
+        .inc();
+    metrics.sponsor_spend_cents_total.inc_by(charge);
+    metrics.record_budget_utilization(
+        "campaign",
+        campaign.id,
+        new_remaining,
+        campaign.budget_total_cents,
+    );
+    if !still_active && let Some(url) = campaign.budget_exhausted_webhook_url.clone() {
+        spawn_budget_exhausted_webhook(
+            metrics.clone(),
+            url,
+            BudgetExhaustedEvent {
+                kind: "campaign",
+                id: campaign.id,
+                sponsor: campaign.sponsor.clone(),
+                budget_remaining_cents: new_remaining,
+                exhausted_at: Utc::now(),
+            },
         );
     }
 
     respond(
         &metrics,
         "/proxy/:service/run",
-        Err::<Response, ApiError>(payment_required_error(
+        Ok(build_paid_tool_response(
+            service,
+            payload,
+            PaymentMode::Sponsored,
+            Some(&campaign),
+            Some(tx_hash),
+            None,
             &config,
-            &service,
-            price,
-            &format!("/proxy/{service}/run"),
-            "no eligible sponsor campaign found",
-            "either complete a sponsor task or pay with PAYMENT-SIGNATURE",
         )),
     )
 }
@@ -889,56 +1842,100 @@ async fn create_sponsored_api(
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<SponsoredApi>)> = async {
-        let (db, http, config) = {
+    let result: ApiResult<Response> = async {
+        let (db, facilitator_http, config, settle_queue) = {
             let state = state.inner.read().await;
-            (state.db.clone(), state.http.clone(), state.config.clone())
+            (
+                state.db.clone(),
+                state.facilitator_http.clone(),
+                state.config.clone(),
+                state.settle_queue.clone(),
+            )
         };
 
-        let db = db.ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        let db = db.ok_or_else(|| ApiError::database_required("creating a sponsored API"))?;
 
         if payload.name.trim().is_empty() {
-            return Err(ApiError::validation("name is required"));
+            return Err(ApiError::validation_field("name", "name is required"));
         }
         if payload.sponsor.trim().is_empty() {
-            return Err(ApiError::validation("sponsor is required"));
+            return Err(ApiError::validation_field("sponsor", "sponsor is required"));
         }
         if payload.budget_cents == 0 {
-            return Err(ApiError::validation("budget_cents must be greater than 0"));
+            return Err(ApiError::validation_field(
+                "budget_cents",
+                "budget_cents must be greater than 0",
+            ));
         }
+        let budget_cents = cents_to_i64("budget_cents", payload.budget_cents)?;
 
-        let price_cents = payload.price_cents.unwrap_or(DEFAULT_PRICE_CENTS);
-        if price_cents == 0 {
-            return Err(ApiError::validation("price_cents must be greater than 0"));
-        }
+        let price_cents = payload.price_cents.unwrap_or(config.default_price_cents);
+        validate_price_cents(&config, "price_cents", price_cents)?;
 
         let upstream_method = normalize_upstream_method(payload.upstream_method)?;
-        reqwest::Url::parse(payload.upstream_url.trim())
-            .map_err(|_| ApiError::validation("upstream_url must be a valid URL"))?;
-
-        for (header, value) in &payload.upstream_headers {
-            HeaderName::from_bytes(header.as_bytes())
-                .map_err(|_| ApiError::validation(format!("invalid upstream header: {header}")))?;
-            HeaderValue::from_str(value).map_err(|_| {
-                ApiError::validation(format!("invalid upstream header value for: {header}"))
+        reqwest::Url::parse(payload.upstream_url.trim()).map_err(|_| {
+            ApiError::validation_field("upstream_url", "upstream_url must be a valid URL")
+        })?;
+
+        validate_upstream_headers(&config, &payload.upstream_headers)?;
+
+        let forward_headers = normalize_forward_headers(payload.forward_headers)?;
+        let success_status_codes = normalize_success_status_codes(payload.success_status_codes)?;
+        let allowed_response_content_types =
+            normalize_content_types(payload.allowed_response_content_types)?;
+        let budget_exhausted_webhook_url = validate_webhook_url(
+            "budget_exhausted_webhook_url",
+            payload.budget_exhausted_webhook_url.as_deref(),
+        )
+        .await?;
+        let x402_scheme = payload
+            .x402_scheme
+            .as_deref()
+            .map(|scheme| {
+                X402Scheme::parse(scheme).ok_or_else(|| {
+                    ApiError::validation_field("x402_scheme", "x402_scheme must be exact or upto")
+                })
+            })
+            .transpose()?;
+        if let Some(schema) = &payload.input_schema {
+            schema::check_schema(schema).map_err(|err| {
+                ApiError::validation_field(
+                    "input_schema",
+                    format!("input_schema is invalid: {err}"),
+                )
             })?;
         }
 
-        if config.sponsored_api_create_price_cents > 0 {
+        // Partner sponsors are onboarded by an operator, so only the management key may waive
+        // the fee.
+        if payload.waive_create_fee {
+            require_management_key(&config, &headers)?;
+        }
+        let waived_fee_cents = if payload.waive_create_fee {
+            config.sponsored_api_create_price_cents
+        } else {
+            0
+        };
+
+        if config.sponsored_api_create_price_cents > 0 && !payload.waive_create_fee {
             let resource_path = "/sponsored-apis".to_string();
-            verify_x402_payment(
-                &http,
-                &config,
+            let payment = verify_x402_payment(
+                &PaymentContext {
+                    http: &facilitator_http,
+                    config: &config,
+                    db: Some(&db),
+                    metrics: &metrics,
+                    settle_queue: settle_queue.as_ref(),
+                },
                 SPONSORED_API_CREATE_SERVICE,
                 config.sponsored_api_create_price_cents,
                 &resource_path,
-                &headers,
+                payment_signature(&headers, None).as_deref(),
+                None,
+                &request_id_from_headers(&headers),
             )
             .await?;
-            metrics
-                .payment_events_total
-                .with_label_values(&["user_direct", "settled"])
-                .inc();
+            count_user_direct_payment(&metrics, &payment);
         }
 
         let api_id = Uuid::new_v4();
@@ -950,133 +1947,314 @@ async fn create_sponsored_api(
             upstream_url: payload.upstream_url,
             upstream_method,
             upstream_headers: payload.upstream_headers,
+            forward_headers,
             price_cents,
             budget_total_cents: payload.budget_cents,
             budget_remaining_cents: payload.budget_cents,
             active: true,
+            charge_on_upstream_error: payload.charge_on_upstream_error,
+            success_status_codes,
+            allowed_response_content_types,
             service_key: sponsored_api_service_key(api_id),
+            created_by: payload
+                .created_by
+                .map(|created_by| created_by.trim().to_string())
+                .filter(|created_by| !created_by.is_empty()),
             created_at: Utc::now(),
+            last_used_at: None,
+            budget_exhausted_webhook_url,
+            x402_scheme,
+            input_schema: payload.input_schema,
+            output_schema: payload.output_schema,
+            follow_redirects: payload.follow_redirects,
+            utilization: 0.0,
+            distinct_callers: None,
         };
 
-        let inserted_row = sqlx::query_as::<_, SponsoredApiRow>(
-            r#"
-            insert into sponsored_apis (
-                id, name, sponsor, description, upstream_url, upstream_method,
-                upstream_headers, price_cents, budget_total_cents, budget_remaining_cents,
-                active, service_key, created_at
-            ) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
-            returning id, name, sponsor, description, upstream_url, upstream_method,
-                upstream_headers, price_cents, budget_total_cents, budget_remaining_cents,
-                active, service_key, created_at
-            "#,
-        )
-        .bind(api.id)
-        .bind(api.name)
-        .bind(api.sponsor)
-        .bind(api.description)
-        .bind(api.upstream_url)
-        .bind(api.upstream_method)
-        .bind(DbJson(api.upstream_headers))
-        .bind(api.price_cents as i64)
-        .bind(api.budget_total_cents as i64)
-        .bind(api.budget_remaining_cents as i64)
-        .bind(api.active)
-        .bind(api.service_key)
-        .bind(api.created_at)
-        .fetch_one(&db)
+        // The creation fee may already have settled, so the insert runs on its own task and
+        // still commits if this request runs past `REQUEST_TIMEOUT_SECS`.
+        let inserted = tokio::spawn(async move {
+            let mut tx = db.begin().await.map_err(ApiError::from_sqlx)?;
+
+            let inserted_row = sqlx::query_as::<_, SponsoredApiRow>(&format!(
+                "insert into sponsored_apis ({SPONSORED_API_COLUMNS}) \
+                 values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, \
+                     $18, $19, $20, $21, $22, $23, $24) \
+                 returning {SPONSORED_API_COLUMNS}"
+            ))
+            .bind(api.id)
+            .bind(api.name)
+            .bind(api.sponsor)
+            .bind(api.description)
+            .bind(api.upstream_url)
+            .bind(api.upstream_method)
+            .bind(DbJson(api.upstream_headers))
+            .bind(api.forward_headers)
+            .bind(cents_to_i64("price_cents", api.price_cents)?)
+            .bind(budget_cents)
+            .bind(budget_cents)
+            .bind(api.active)
+            .bind(api.charge_on_upstream_error)
+            .bind(
+                api.success_status_codes
+                    .iter()
+                    .map(|code| i32::from(*code))
+                    .collect::<Vec<_>>(),
+            )
+            .bind(api.allowed_response_content_types)
+            .bind(api.service_key)
+            .bind(api.created_by)
+            .bind(api.created_at)
+            .bind(api.last_used_at)
+            .bind(api.budget_exhausted_webhook_url)
+            .bind(api.x402_scheme.map(X402Scheme::as_str))
+            .bind(api.input_schema.map(DbJson))
+            .bind(api.output_schema.map(DbJson))
+            .bind(api.follow_redirects)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(ApiError::from_sqlx)?;
+
+            let inserted = SponsoredApi::try_from(inserted_row)
+                .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+            record_audit(
+                &mut tx,
+                &AuditEntry {
+                    id: Uuid::new_v4(),
+                    actor: inserted.created_by.clone(),
+                    action: "create".to_string(),
+                    entity_type: "sponsored_api".to_string(),
+                    entity_id: inserted.id,
+                    before: None,
+                    after: Some(sponsored_api_audit_snapshot(&inserted)),
+                    created_at: Utc::now(),
+                },
+            )
+            .await?;
+            if waived_fee_cents > 0 {
+                record_audit(
+                    &mut tx,
+                    &AuditEntry {
+                        id: Uuid::new_v4(),
+                        actor: inserted.created_by.clone(),
+                        action: "waive_create_fee".to_string(),
+                        entity_type: "sponsored_api".to_string(),
+                        entity_id: inserted.id,
+                        before: None,
+                        after: Some(serde_json::json!({
+                            "sponsor": inserted.sponsor,
+                            "waived_cents": waived_fee_cents,
+                        })),
+                        created_at: Utc::now(),
+                    },
+                )
+                .await?;
+            }
+
+            tx.commit().await.map_err(ApiError::from_sqlx)?;
+            Ok::<_, ApiError>(inserted)
+        })
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(|err| ApiError::internal(format!("sponsored api insert failed: {err}")))??;
 
-        let inserted = SponsoredApi::try_from(inserted_row)
-            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
-        Ok((StatusCode::CREATED, Json(inserted)))
+        Ok(created_response(
+            &format!("/sponsored-apis/{}", inserted.id),
+            inserted,
+        ))
     }
     .await;
 
     respond(&metrics, "/sponsored-apis", result)
 }
 
-async fn list_sponsored_apis(State(state): State<SharedState>) -> Response {
+async fn list_sponsored_apis(
+    State(state): State<SharedState>,
+    Query(query): Query<ListOrderQuery>,
+) -> Response {
     let metrics = {
         let state = state.inner.read().await;
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<Vec<SponsoredApi>>)> = async {
+    let result: ApiResult<Response> = async {
         let db = {
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("listing sponsored APIs"))?;
 
-        let api_rows = sqlx::query_as::<_, SponsoredApiRow>(
-            r#"
-            select id, name, sponsor, description, upstream_url, upstream_method,
-                upstream_headers, price_cents, budget_total_cents, budget_remaining_cents,
-                active, service_key, created_at
-            from sponsored_apis
-            order by created_at desc
-            "#,
-        )
-        .fetch_all(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        let total = sqlx::query_scalar::<_, i64>("select count(*) from sponsored_apis")
+            .fetch_one(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?;
+        let order_by = match query.order {
+            ListOrder::Created => "order by created_at desc",
+            ListOrder::LastUsed => "order by last_used_at desc nulls last, created_at desc",
+        };
+        let api_rows = sqlx::query_as::<_, SponsoredApiRow>(&sponsored_api_select(order_by))
+            .fetch_all(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?;
 
-        let apis: Vec<SponsoredApi> = api_rows
-            .into_iter()
-            .map(SponsoredApi::try_from)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+        let (apis, skipped): (Vec<SponsoredApi>, _) =
+            convert_list_rows(api_rows, "sponsored api", |row: &SponsoredApiRow| row.id);
 
-        Ok((StatusCode::OK, Json(apis)))
+        Ok(counted_list_response(apis, skipped, total))
     }
     .await;
 
     respond(&metrics, "/sponsored-apis", result)
 }
 
-async fn get_sponsored_api(State(state): State<SharedState>, Path(api_id): Path<Uuid>) -> Response {
+async fn sponsored_api_create_price(State(state): State<SharedState>) -> Response {
+    let (metrics, config) = {
+        let state = state.inner.read().await;
+        (state.metrics.clone(), state.config.clone())
+    };
+
+    let result: ApiResult<(StatusCode, Json<SponsoredApiCreatePrice>)> = async {
+        let price_cents = config.sponsored_api_create_price_cents;
+        let requirements = if price_cents > 0 {
+            build_payment_requirements(
+                &config,
+                SPONSORED_API_CREATE_SERVICE,
+                price_cents,
+                "/sponsored-apis",
+            )?
+        } else {
+            Vec::new()
+        };
+
+        Ok((
+            StatusCode::OK,
+            Json(SponsoredApiCreatePrice {
+                service: SPONSORED_API_CREATE_SERVICE.to_string(),
+                x402_version: config.x402_version.clone(),
+                price_cents,
+                requirements,
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/sponsored-apis/create-price", result)
+}
+
+async fn get_sponsored_api(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(api_id): Path<Uuid>,
+) -> Response {
     let metrics = {
         let state = state.inner.read().await;
         state.metrics.clone()
     };
 
-    let result: ApiResult<(StatusCode, Json<SponsoredApi>)> = async {
+    let result: ApiResult<Response> = async {
         let db = {
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("fetching a sponsored API"))?;
+
+        let row = sqlx::query_as::<_, UpdatedRow<SponsoredApiRow>>(&format!(
+            "select {SPONSORED_API_COLUMNS}, updated_at from sponsored_apis where id = $1"
+        ))
+        .bind(api_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .ok_or_else(|| ApiError::not_found("sponsored api not found"))?;
+        let mut api = SponsoredApi::try_from(row.row)
+            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+        let distinct_callers = sqlx::query_scalar::<_, i64>(
+            "select count(distinct caller) from sponsored_api_calls where sponsored_api_id = $1",
+        )
+        .bind(api_id)
+        .fetch_one(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+        api.distinct_callers = Some(distinct_callers as u64);
+
+        Ok(conditional_response(&headers, row.updated_at, api))
+    }
+    .await;
+
+    respond(&metrics, "/sponsored-apis/:api_id", result)
+}
+
+async fn list_sponsored_api_calls(
+    State(state): State<SharedState>,
+    Path(api_id): Path<Uuid>,
+    Query(query): Query<SponsoredApiCallsQuery>,
+) -> Response {
+    let (metrics, db) = {
+        let state = state.inner.read().await;
+        (state.metrics.clone(), state.db.clone())
+    };
+
+    let result: ApiResult<Response> = async {
+        let db = db.ok_or_else(|| ApiError::database_required("listing sponsored API calls"))?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_SPONSORED_API_CALLS_LIMIT)
+            .clamp(1, MAX_SPONSORED_API_CALLS_LIMIT);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            "select exists(select 1 from sponsored_apis where id = $1)",
+        )
+        .bind(api_id)
+        .fetch_one(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+        if !exists {
+            return Err(ApiError::not_found("sponsored api not found"));
+        }
 
-        let api = sqlx::query_as::<_, SponsoredApiRow>(
+        let rows = sqlx::query_as::<_, SponsoredApiCallRow>(
             r#"
-            select id, name, sponsor, description, upstream_url, upstream_method,
-                upstream_headers, price_cents, budget_total_cents, budget_remaining_cents,
-                active, service_key, created_at
-            from sponsored_apis
-            where id = $1
+            select id, sponsored_api_id, payment_mode, amount_cents, tx_hash, caller, created_at
+            from sponsored_api_calls
+            where sponsored_api_id = $1
+              and ($2::text is null or payment_mode = $2)
+              and ($3::text is null or caller = $3)
+              and ($4::timestamptz is null or created_at >= $4)
+              and ($5::timestamptz is null or created_at < $5)
+            order by created_at desc, id
+            limit $6 offset $7
             "#,
         )
         .bind(api_id)
-        .fetch_optional(&db)
+        .bind(query.payment_mode.map(|mode| mode.as_db_str()))
+        .bind(query.caller.as_deref())
+        .bind(query.since)
+        .bind(query.until)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-        .ok_or_else(|| ApiError::not_found("sponsored api not found"))
-        .and_then(|row| {
-            SponsoredApi::try_from(row)
-                .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
-        })?;
+        .map_err(ApiError::from_sqlx)?;
+
+        let (calls, skipped): (Vec<SponsoredApiCall>, _) =
+            convert_list_rows(rows, "sponsored api call", |row: &SponsoredApiCallRow| {
+                row.id
+            });
 
-        Ok((StatusCode::OK, Json(api)))
+        Ok(list_response(calls, skipped))
     }
     .await;
 
-    respond(&metrics, "/sponsored-apis/:api_id", result)
+    respond(&metrics, "/sponsored-apis/:api_id/calls", result)
 }
 
 async fn run_sponsored_api(
     State(state): State<SharedState>,
     Path(api_id): Path<Uuid>,
+    Query(query): Query<SponsoredApiRunQuery>,
     headers: HeaderMap,
     Json(payload): Json<SponsoredApiRunRequest>,
 ) -> Response {
@@ -1086,57 +2264,102 @@ async fn run_sponsored_api(
     };
 
     let result: ApiResult<Response> = async {
-        let (db, http, config) = {
+        let (db, http, facilitator_http, config, settle_queue) = {
             let state = state.inner.read().await;
-            (state.db.clone(), state.http.clone(), state.config.clone())
+            (
+                state.db.clone(),
+                state.http.clone(),
+                state.facilitator_http.clone(),
+                state.config.clone(),
+                state.settle_queue.clone(),
+            )
         };
 
-        let db = db.ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        let db = db.ok_or_else(|| ApiError::database_required("running a sponsored API"))?;
 
-        let api = sqlx::query_as::<_, SponsoredApiRow>(
-            r#"
-            select id, name, sponsor, description, upstream_url, upstream_method,
-                upstream_headers, price_cents, budget_total_cents, budget_remaining_cents,
-                active, service_key, created_at
-            from sponsored_apis
-            where id = $1
-            "#,
-        )
-        .bind(api_id)
-        .fetch_optional(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-        .ok_or_else(|| ApiError::not_found("sponsored api not found"))
-        .and_then(|row| {
-            SponsoredApi::try_from(row)
-                .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
-        })?;
+        let api = sqlx::query_as::<_, SponsoredApiRow>(&sponsored_api_select("where id = $1"))
+            .bind(api_id)
+            .fetch_optional(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?
+            .ok_or_else(|| ApiError::not_found("sponsored api not found"))
+            .and_then(|row| {
+                SponsoredApi::try_from(row)
+                    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
+            })?;
+        // Challenges and verification for this API use its own scheme when it sets one, and
+        // advertise its own contract.
+        let mut config = AppConfig {
+            x402_scheme: api.x402_scheme.unwrap_or(config.x402_scheme),
+            ..config
+        };
+        if let Some(output_schema) =
+            x402_output_schema(api.input_schema.as_ref(), api.output_schema.as_ref())
+        {
+            config
+                .x402_output_schemas
+                .insert(api.service_key.clone(), output_schema);
+        }
+
+        let upstream_timeout_secs = match payload.timeout_secs {
+            None => config.sponsored_api_timeout_secs,
+            Some(secs) if (1..=config.sponsored_api_max_timeout_secs).contains(&secs) => secs,
+            Some(_) => {
+                return Err(ApiError::validation_field(
+                    "timeout_secs",
+                    format!(
+                        "timeout_secs must be between 1 and {}",
+                        config.sponsored_api_max_timeout_secs
+                    ),
+                ));
+            }
+        };
 
+        // Checked before anyone pays, since the upstream would reject this input anyway.
+        if let Some(schema) = &api.input_schema {
+            let errors = schema::validate(schema, &payload.input);
+            if !errors.is_empty() {
+                return Err(ApiError::validation_field(
+                    "input",
+                    format!("input does not match input_schema: {}", errors.join("; ")),
+                )
+                .with_details(serde_json::json!({ "field": "input", "errors": errors })));
+            }
+        }
+
+        let request_id = request_id_from_headers(&headers);
         let price = api.price_cents;
         let service_key = api.service_key.clone();
-        let mut payment_mode = "sponsored".to_string();
+        let mut payment_mode = PaymentMode::Sponsored;
         let mut sponsored_by = None;
         let mut tx_hash: Option<String> = None;
+        // Set when this call's debit leaves less than one more call's price.
+        let mut exhausted_remaining: Option<u64> = None;
         let mut payment_response_header: Option<String> = None;
 
-        if headers.contains_key(PAYMENT_SIGNATURE_HEADER) {
+        let signature = payment_signature(&headers, payload.payment.as_deref());
+        if signature.is_some() {
             let resource_path = format!("/sponsored-apis/{api_id}/run");
             let payment = verify_x402_payment(
-                &http,
-                &config,
+                &PaymentContext {
+                    http: &facilitator_http,
+                    config: &config,
+                    db: Some(&db),
+                    metrics: &metrics,
+                    settle_queue: settle_queue.as_ref(),
+                },
                 &service_key,
                 price,
                 &resource_path,
-                &headers,
+                signature.as_deref(),
+                None,
+                &request_id,
             )
             .await?;
-            metrics
-                .payment_events_total
-                .with_label_values(&["user_direct", "settled"])
-                .inc();
-            payment_mode = "user_direct".to_string();
+            count_user_direct_payment(&metrics, &payment);
+            payment_mode = PaymentMode::UserDirect;
             tx_hash = payment.tx_hash;
-            payment_response_header = Some(payment.payment_response_header);
+            payment_response_header = payment.payment_response_header;
         } else if api.active && api.budget_remaining_cents >= price {
             let new_remaining = api.budget_remaining_cents.saturating_sub(price);
             let still_active = new_remaining >= price && new_remaining > 0;
@@ -1144,24 +2367,27 @@ async fn run_sponsored_api(
             sqlx::query(
                 r#"
                 update sponsored_apis
-                set budget_remaining_cents = $1, active = $2
-                where id = $3
+                set budget_remaining_cents = $1, active = $2, last_used_at = $3
+                where id = $4
                 "#,
             )
             .bind(new_remaining as i64)
             .bind(still_active)
+            .bind(Utc::now())
             .bind(api.id)
             .execute(&db)
             .await
-            .map_err(|err| {
-                ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
-            })?;
+            .map_err(ApiError::from_sqlx)?;
+            metrics.record_budget_utilization(
+                "sponsored_api",
+                api.id,
+                new_remaining,
+                api.budget_total_cents,
+            );
+            if !still_active {
+                exhausted_remaining = Some(new_remaining);
+            }
 
-            metrics
-                .payment_events_total
-                .with_label_values(&["sponsored", "settled"])
-                .inc();
-            metrics.sponsor_spend_cents_total.inc_by(price);
             sponsored_by = Some(api.sponsor.clone());
         } else {
             return Err(payment_required_error(
@@ -1171,62 +2397,141 @@ async fn run_sponsored_api(
                 &format!("/sponsored-apis/{api_id}/run"),
                 "sponsored budget exhausted",
                 "pay with PAYMENT-SIGNATURE and retry",
-            ));
+            )
+            // Lets the client tell a deactivated API (pay now) from one that is only short.
+            .with_details(serde_json::json!({
+                "active": api.active,
+                "budget_remaining_cents": api.budget_remaining_cents,
+            })));
+        }
+
+        let SponsoredApiRunRequest { caller, input, .. } = payload;
+        let upstream_started = Instant::now();
+        let upstream = send_upstream(
+            &http,
+            &api,
+            input,
+            &headers,
+            upstream_timeout_secs,
+            config.sponsored_api_max_redirects,
+            &request_id,
+        )
+        .await;
+        let mut content_type_rejected = false;
+        let upstream = upstream.and_then(|response| {
+            ensure_allowed_content_type(&api, &response)
+                .inspect_err(|_| content_type_rejected = true)?;
+            Ok(response)
+        });
+
+        // Unless the sponsor opted in to paying for upstream failures, the debit taken above is
+        // handed back. A response we refuse to relay is never billed. A user's x402 payment has
+        // already settled and cannot be returned.
+        let upstream_failed = match &upstream {
+            Ok(response) => !api.counts_as_success(response.status()),
+            Err(_) => true,
+        };
+        let refund = content_type_rejected || (upstream_failed && !api.charge_on_upstream_error);
+        let charged = if sponsored_by.is_some() && refund {
+            match refund_sponsored_api_budget(&db, &api).await {
+                Ok(()) => {
+                    metrics.record_budget_utilization(
+                        "sponsored_api",
+                        api.id,
+                        api.budget_remaining_cents,
+                        api.budget_total_cents,
+                    );
+                    false
+                }
+                Err(err) => {
+                    warn!("failed to refund sponsored api {} budget: {err}", api.id);
+                    true
+                }
+            }
+        } else {
+            true
+        };
+        if sponsored_by.is_some() && charged {
+            metrics
+                .payment_events_total
+                .with_label_values(&[
+                    PaymentMode::Sponsored.as_str(),
+                    PaymentStatus::Settled.as_db_str(),
+                ])
+                .inc();
+            metrics.sponsor_spend_cents_total.inc_by(price);
+        }
+        // A refunded call hands the budget back, so it did not exhaust anything.
+        if charged
+            && let Some(remaining) = exhausted_remaining
+            && let Some(url) = api.budget_exhausted_webhook_url.clone()
+        {
+            spawn_budget_exhausted_webhook(
+                metrics.clone(),
+                url,
+                BudgetExhaustedEvent {
+                    kind: "sponsored_api",
+                    id: api.id,
+                    sponsor: api.sponsor.clone(),
+                    budget_remaining_cents: remaining,
+                    exhausted_at: Utc::now(),
+                },
+            );
+        }
+
+        // The sponsored debit already stamps `last_used_at`. A paid call only counts as use once
+        // the upstream answered, and the payment has settled, so a failed write must not fail it.
+        if payment_mode == PaymentMode::UserDirect && !upstream_failed {
+            spawn_sponsored_api_use(db.clone(), api.id);
         }
 
-        let SponsoredApiRunRequest { caller, input } = payload;
-        let (upstream_status, upstream_body) =
-            call_upstream(&http, &api, input, config.sponsored_api_timeout_secs).await?;
+        let upstream = upstream?;
+        let upstream_status = upstream.status().as_u16();
 
         let call_log = SponsoredApiCall {
             id: Uuid::new_v4(),
             sponsored_api_id: api.id,
-            payment_mode: payment_mode.clone(),
-            amount_cents: price,
+            payment_mode,
+            amount_cents: if charged { price } else { 0 },
             tx_hash: tx_hash.clone(),
             caller,
             created_at: Utc::now(),
         };
 
-        sqlx::query(
-            r#"
-            insert into sponsored_api_calls (
-                id, sponsored_api_id, payment_mode, amount_cents, tx_hash, caller, created_at
-            ) values ($1, $2, $3, $4, $5, $6, $7)
-            "#,
-        )
-        .bind(call_log.id)
-        .bind(call_log.sponsored_api_id)
-        .bind(call_log.payment_mode)
-        .bind(call_log.amount_cents as i64)
-        .bind(call_log.tx_hash)
-        .bind(call_log.caller)
-        .bind(call_log.created_at)
-        .execute(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-
-        let response_payload = SponsoredApiRunResponse {
-            api_id: api.id,
-            payment_mode,
-            sponsored_by,
-            tx_hash,
-            upstream_status,
-            upstream_body,
+        // Streaming hands the upstream body through untouched, so the envelope fields that
+        // still matter travel as headers instead.
+        let mut response = if query.stream {
+            let mut response = streamed_upstream_response(upstream);
+            response.headers_mut().insert(
+                HeaderName::from_static(PAYMENT_MODE_HEADER),
+                HeaderValue::from_static(payment_mode.as_str()),
+            );
+            response
+        } else {
+            let upstream_body = upstream.text().await.unwrap_or_default();
+            let response_payload = SponsoredApiRunResponse {
+                api_id: api.id,
+                payment_mode,
+                sponsored_by,
+                tx_hash,
+                charged,
+                upstream_status,
+                upstream_duration_ms: upstream_started.elapsed().as_millis() as u64,
+                upstream_body,
+            };
+            let status = if charged {
+                StatusCode::OK
+            } else {
+                StatusCode::from_u16(upstream_status).unwrap_or(StatusCode::BAD_GATEWAY)
+            };
+            (status, Json(response_payload)).into_response()
         };
 
-        let mut response = (StatusCode::OK, Json(response_payload)).into_response();
-        response.headers_mut().insert(
-            HeaderName::from_static(X402_VERSION_HEADER),
-            HeaderValue::from_static("2"),
-        );
+        spawn_sponsored_call_log(db.clone(), metrics.clone(), call_log);
+
+        insert_x402_version_header(response.headers_mut(), &config.x402_version);
         if let Some(settlement_header) = payment_response_header {
-            if let Ok(header_value) = HeaderValue::from_str(&settlement_header) {
-                response.headers_mut().insert(
-                    HeaderName::from_static(PAYMENT_RESPONSE_HEADER),
-                    header_value,
-                );
-            }
+            insert_payment_response_headers(response.headers_mut(), &config, &settlement_header);
         }
 
         Ok(response)
@@ -1240,58 +2545,26 @@ async fn ingest_x402scan_settlement(
     State(state): State<SharedState>,
     Json(payload): Json<X402ScanSettlementRequest>,
 ) -> Response {
-    let metrics = {
+    let (metrics, db, config) = {
         let state = state.inner.read().await;
-        state.metrics.clone()
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
     };
 
     let result: ApiResult<(StatusCode, Json<MessageResponse>)> = async {
-        let db = {
-            let state = state.inner.read().await;
-            state.db.clone()
-        }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
-
-        let source_str = match payload.source {
-            PaymentSource::User => "user",
-            PaymentSource::Sponsor => "sponsor",
-        };
-        let status_str = match payload.status {
-            PaymentStatus::Settled => "settled",
-            PaymentStatus::Failed => "failed",
-        };
-
-        sqlx::query(
-            r#"
-            insert into payments (tx_hash, campaign_id, service, amount_cents, payer, source, status, created_at)
-            values ($1, $2, $3, $4, $5, $6, $7, $8)
-            on conflict (tx_hash) do nothing
-            "#,
-        )
-        .bind(&payload.tx_hash)
-        .bind(payload.campaign_id)
-        .bind(&payload.service)
-        .bind(payload.amount_cents as i64)
-        .bind(&payload.payer)
-        .bind(source_str)
-        .bind(status_str)
-        .bind(Utc::now())
-        .execute(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        let db = db.ok_or_else(|| ApiError::database_required("ingesting x402scan settlements"))?;
 
-        let mode = match payload.source {
-            PaymentSource::User => "user_direct",
-            PaymentSource::Sponsor => "sponsored",
-        };
-        let status = match payload.status {
-            PaymentStatus::Settled => "settled",
-            PaymentStatus::Failed => "failed",
-        };
+        insert_x402scan_settlement(&db, &config.x402_network, &payload).await?;
 
         metrics
             .payment_events_total
-            .with_label_values(&[mode, status])
+            .with_label_values(&[
+                payload.source.payment_mode().as_str(),
+                payload.status.as_db_str(),
+            ])
             .inc();
 
         Ok((
@@ -1306,6 +2579,59 @@ async fn ingest_x402scan_settlement(
     respond(&metrics, "/webhooks/x402scan/settlement", result)
 }
 
+async fn ingest_x402scan_settlement_batch(
+    State(state): State<SharedState>,
+    Json(payload): Json<Vec<X402ScanSettlementRequest>>,
+) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
+
+    let result: ApiResult<(StatusCode, Json<X402ScanBatchResponse>)> = async {
+        if payload.len() > MAX_SETTLEMENT_BATCH {
+            return Err(ApiError::validation(format!(
+                "at most {MAX_SETTLEMENT_BATCH} settlements can be ingested at once"
+            )));
+        }
+        let db = db.ok_or_else(|| ApiError::database_required("ingesting x402scan settlements"))?;
+
+        let mut tx = db.begin().await.map_err(ApiError::from_sqlx)?;
+        let mut inserted = Vec::with_capacity(payload.len());
+        for settlement in &payload {
+            if insert_x402scan_settlement(&mut *tx, &config.x402_network, settlement).await? {
+                inserted.push(settlement);
+            }
+        }
+        tx.commit().await.map_err(ApiError::from_sqlx)?;
+
+        for settlement in &inserted {
+            metrics
+                .payment_events_total
+                .with_label_values(&[
+                    settlement.source.payment_mode().as_str(),
+                    settlement.status.as_db_str(),
+                ])
+                .inc();
+        }
+
+        Ok((
+            StatusCode::ACCEPTED,
+            Json(X402ScanBatchResponse {
+                inserted: inserted.len(),
+                skipped: payload.len() - inserted.len(),
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/webhooks/x402scan/settlement:batch", result)
+}
+
 async fn sponsor_dashboard(
     State(state): State<SharedState>,
     Path(campaign_id): Path<Uuid>,
@@ -1320,23 +2646,15 @@ async fn sponsor_dashboard(
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("reading the sponsor dashboard"))?;
 
         // Load campaign from database
-        let campaign_row = sqlx::query_as::<_, CampaignRow>(
-            r#"
-            select id, name, sponsor, target_roles, target_tools, required_task,
-                subsidy_per_call_cents, budget_total_cents, budget_remaining_cents,
-                query_urls, active, created_at
-            from campaigns
-            where id = $1
-            "#,
-        )
-        .bind(campaign_id)
-        .fetch_optional(&db)
-        .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-        .ok_or_else(|| ApiError::not_found("campaign not found"))?;
+        let campaign_row = sqlx::query_as::<_, CampaignRow>(&campaign_select("where id = $1"))
+            .bind(campaign_id)
+            .fetch_optional(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?
+            .ok_or_else(|| ApiError::not_found("campaign not found"))?;
 
         let campaign = Campaign::try_from(campaign_row)
             .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
@@ -1348,8 +2666,7 @@ async fn sponsor_dashboard(
         .bind(campaign_id)
         .fetch_one(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-            as usize;
+        .map_err(ApiError::from_sqlx)? as usize;
 
         // Get sponsored payments
         let payment_amounts: Vec<i64> = sqlx::query_scalar::<_, i64>(
@@ -1364,7 +2681,21 @@ async fn sponsor_dashboard(
         .bind(campaign_id)
         .fetch_all(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
+
+        let distinct_users = sqlx::query_scalar::<_, i64>(
+            r#"
+            select count(distinct user_id)
+            from payments
+            where campaign_id = $1
+              and source = 'sponsor'
+              and status = 'settled'
+            "#,
+        )
+        .bind(campaign_id)
+        .fetch_one(&db)
+        .await
+        .map_err(ApiError::from_sqlx)? as u64;
 
         let sponsored_calls = payment_amounts.len();
         let spend_cents: u64 = payment_amounts
@@ -1377,6 +2708,7 @@ async fn sponsor_dashboard(
             campaign,
             tasks_completed,
             sponsored_calls,
+            distinct_users,
             spend_cents,
         };
 
@@ -1401,7 +2733,7 @@ async fn record_creator_metric_event(
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("recording creator metrics"))?;
 
         let event = CreatorEvent {
             id: Uuid::new_v4(),
@@ -1428,7 +2760,7 @@ async fn record_creator_metric_event(
         .bind(event.created_at)
         .execute(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
 
         metrics
             .creator_events_total
@@ -1453,22 +2785,20 @@ async fn creator_metrics(State(state): State<SharedState>) -> Response {
             let state = state.inner.read().await;
             state.db.clone()
         }
-        .ok_or_else(|| ApiError::config("Postgres not configured; set DATABASE_URL"))?;
+        .ok_or_else(|| ApiError::database_required("reading creator metrics"))?;
 
         // Get total events and success events
         let total_events = sqlx::query_scalar::<_, i64>("select count(*) from creator_events")
             .fetch_one(&db)
             .await
-            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-            as usize;
+            .map_err(ApiError::from_sqlx)? as usize;
 
         let success_events = sqlx::query_scalar::<_, i64>(
             "select count(*) from creator_events where success = true",
         )
         .fetch_one(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
-            as usize;
+        .map_err(ApiError::from_sqlx)? as usize;
 
         let success_rate = if total_events == 0 {
             0.0
@@ -1476,61 +2806,421 @@ async fn creator_metrics(State(state): State<SharedState>) -> Response {
             success_events as f64 / total_events as f64
         };
 
-        // Get per-skill metrics
-        #[derive(sqlx::FromRow)]
-        struct SkillMetricsRow {
-            skill_name: String,
-            total_events: i64,
-            success_events: i64,
-            avg_duration_ms: Option<f64>,
-            last_seen_at: chrono::DateTime<chrono::Utc>,
-        }
+        let per_skill = load_skill_metrics(&db).await?;
+
+        Ok((
+            StatusCode::OK,
+            Json(CreatorMetricSummary {
+                total_events,
+                success_events,
+                success_rate,
+                per_skill,
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/creator/metrics", result)
+}
+
+async fn admin_stats(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
+
+    let result: ApiResult<(StatusCode, Json<AdminStats>)> = async {
+        require_management_key(&config, &headers)?;
+        let db = db.ok_or_else(|| ApiError::database_required("reading admin stats"))?;
+
+        let row = sqlx::query_as::<_, AdminStatsRow>(
+            r#"
+            select
+                (select count(*) from campaigns) as total_campaigns,
+                (select count(*) from campaigns where active = true) as active_campaigns,
+                (select coalesce(sum(amount_cents), 0)::bigint from payments
+                    where source = 'sponsor' and status = 'settled') as sponsored_spend_cents,
+                (select coalesce(sum(amount_cents), 0)::bigint from payments
+                    where source = 'user' and status = 'settled') as user_direct_spend_cents,
+                (select count(*) from sponsored_api_calls) as sponsored_api_calls,
+                (select count(*) from users) as total_users
+            "#,
+        )
+        .fetch_one(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+
+        let mut stats = AdminStats::try_from(row)
+            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+        stats.spend_by_network = sqlx::query_as::<_, NetworkSpendRow>(
+            r#"
+            select
+                network,
+                scheme,
+                source,
+                coalesce(sum(amount_cents), 0)::bigint as spend_cents,
+                count(*) as payment_count
+            from payments
+            where status = 'settled'
+              and network is not null
+              and scheme is not null
+            group by network, scheme, source
+            order by spend_cents desc
+            "#,
+        )
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .into_iter()
+        .map(NetworkSpend::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+        Ok((StatusCode::OK, Json(stats)))
+    }
+    .await;
+
+    respond(&metrics, "/admin/stats", result)
+}
+
+async fn admin_top_sponsors(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<TopSponsorsQuery>,
+) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
 
-        let skill_rows = sqlx::query_as::<_, SkillMetricsRow>(
+    let result: ApiResult<(StatusCode, Json<Vec<TopSponsor>>)> = async {
+        require_management_key(&config, &headers)?;
+        let db = db.ok_or_else(|| ApiError::database_required("ranking top sponsors"))?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_TOP_SPONSORS_LIMIT)
+            .clamp(1, MAX_TOP_SPONSORS_LIMIT);
+
+        let rows = sqlx::query_as::<_, TopSponsorRow>(
             r#"
             select
-                skill_name,
-                count(*) as total_events,
-                count(*) filter (where success = true) as success_events,
-                avg(duration_ms) as avg_duration_ms,
-                max(created_at) as last_seen_at
-            from creator_events
-            group by skill_name
-            order by total_events desc, last_seen_at desc
+                c.sponsor,
+                coalesce(sum(p.amount_cents), 0)::bigint as spend_cents,
+                count(*) as call_count
+            from payments p
+            join campaigns c on c.id = p.campaign_id
+            where p.source = 'sponsor'
+              and p.status = 'settled'
+              and ($1::timestamptz is null or p.created_at >= $1)
+            group by c.sponsor
+            order by spend_cents desc, c.sponsor
+            limit $2
             "#,
         )
+        .bind(query.since)
+        .bind(limit)
         .fetch_all(&db)
         .await
-        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+        .map_err(ApiError::from_sqlx)?;
 
-        let per_skill: Vec<SkillMetrics> = skill_rows
+        let sponsors: Vec<TopSponsor> = rows
             .into_iter()
-            .map(|row| SkillMetrics {
-                skill_name: row.skill_name,
-                total_events: row.total_events as usize,
-                success_events: row.success_events as usize,
-                avg_duration_ms: row.avg_duration_ms,
-                last_seen_at: row.last_seen_at,
-            })
+            .map(TopSponsor::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+        Ok((StatusCode::OK, Json(sponsors)))
+    }
+    .await;
+
+    respond(&metrics, "/admin/top-sponsors", result)
+}
+
+async fn admin_proxy_decisions(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<ProxyDecisionsQuery>,
+) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
+
+    let result: ApiResult<(StatusCode, Json<ProxyDecisionCounts>)> = async {
+        require_management_key(&config, &headers)?;
+        let db = db.ok_or_else(|| ApiError::database_required("counting proxy decisions"))?;
+
+        let rows = sqlx::query_as::<_, ProxyDecisionCountRow>(
+            r#"
+            select decision, count(*) as count
+            from proxy_decisions
+            where ($1::timestamptz is null or created_at >= $1)
+              and ($2::timestamptz is null or created_at < $2)
+            group by decision
+            "#,
+        )
+        .bind(query.since)
+        .bind(query.until)
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+
+        let mut decisions: BTreeMap<&'static str, u64> = ProxyDecision::ALL
+            .iter()
+            .map(|decision| (decision.as_db_str(), 0))
             .collect();
+        for row in rows {
+            let Some(decision) = ProxyDecision::ALL
+                .into_iter()
+                .find(|decision| decision.as_db_str() == row.decision)
+            else {
+                warn!("ignoring unknown proxy decision '{}'", row.decision);
+                continue;
+            };
+            let count = u64::try_from(row.count).map_err(|_| {
+                ApiError::database(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "count must be non-negative".to_string(),
+                )
+            })?;
+            decisions.insert(decision.as_db_str(), count);
+        }
 
         Ok((
             StatusCode::OK,
-            Json(CreatorMetricSummary {
-                total_events,
-                success_events,
-                success_rate,
-                per_skill,
+            Json(ProxyDecisionCounts {
+                total: decisions.values().sum(),
+                decisions,
             }),
         ))
     }
     .await;
 
-    respond(&metrics, "/creator/metrics", result)
+    respond(&metrics, "/admin/proxy-decisions", result)
+}
+
+async fn admin_audit_log(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditLogQuery>,
+) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
+
+    let result: ApiResult<(StatusCode, Json<Vec<AuditEntry>>)> = async {
+        require_management_key(&config, &headers)?;
+        let db = db.ok_or_else(|| ApiError::database_required("reading the audit log"))?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_LOG_LIMIT)
+            .clamp(1, MAX_AUDIT_LOG_LIMIT);
+
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            r#"
+            select id, actor, action, entity_type, entity_id, before, after, created_at
+            from audit_log
+            where ($1::text is null or entity_type = $1)
+              and ($2::uuid is null or entity_id = $2)
+              and ($3::timestamptz is null or created_at >= $3)
+              and ($4::timestamptz is null or created_at < $4)
+            order by created_at desc, id
+            limit $5
+            "#,
+        )
+        .bind(query.entity_type.as_deref())
+        .bind(query.entity_id)
+        .bind(query.since)
+        .bind(query.until)
+        .bind(limit)
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+
+        Ok((StatusCode::OK, Json(entries)))
+    }
+    .await;
+
+    respond(&metrics, "/admin/audit", result)
 }
 
-async fn prometheus_metrics(State(state): State<SharedState>) -> Response {
+async fn admin_config(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    let (metrics, config) = {
+        let state = state.inner.read().await;
+        (state.metrics.clone(), state.config.clone())
+    };
+
+    let result: ApiResult<(StatusCode, Json<ConfigSnapshot>)> = async {
+        require_management_key(&config, &headers)?;
+        Ok((StatusCode::OK, Json(ConfigSnapshot::from(&config))))
+    }
+    .await;
+
+    respond(&metrics, "/admin/config", result)
+}
+
+async fn admin_reconcile(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(payload): Json<ReconcileRequest>,
+) -> Response {
+    let (metrics, db, config) = {
+        let state = state.inner.read().await;
+        (
+            state.metrics.clone(),
+            state.db.clone(),
+            state.config.clone(),
+        )
+    };
+
+    let result: ApiResult<(StatusCode, Json<ReconcileReport>)> = async {
+        require_management_key(&config, &headers)?;
+        if payload.settlements.len() > MAX_RECONCILE_ENTRIES {
+            return Err(ApiError::validation_field(
+                "settlements",
+                format!("at most {MAX_RECONCILE_ENTRIES} settlements can be reconciled at once"),
+            ));
+        }
+        let db = db.ok_or_else(|| ApiError::database_required("reconciling payments"))?;
+
+        // Payments are deduplicated by tx_hash, so a repeated hash in the export is one payment.
+        let mut seen = HashSet::new();
+        let settlements: Vec<ReconcileEntry> = payload
+            .settlements
+            .into_iter()
+            .filter(|entry| seen.insert(entry.tx_hash.clone()))
+            .collect();
+        let tx_hashes: Vec<String> = settlements
+            .iter()
+            .map(|entry| entry.tx_hash.clone())
+            .collect();
+
+        let recorded: HashMap<String, RecordedPayment> = sqlx::query_as::<_, RecordedPaymentRow>(
+            "select tx_hash, amount_cents, status from payments where tx_hash = any($1)",
+        )
+        .bind(&tx_hashes)
+        .fetch_all(&db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .into_iter()
+        .map(|row| RecordedPayment::try_from(row).map(|row| (row.tx_hash.clone(), row)))
+        .collect::<Result<_, _>>()
+        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+        // Without `since`, the export's window is taken to start at its oldest matched payment;
+        // an export that matches nothing locally then has no window to report extras from.
+        let since = match payload.since {
+            Some(since) => Some(since),
+            None => sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+                "select min(created_at) from payments where tx_hash = any($1)",
+            )
+            .bind(&tx_hashes)
+            .fetch_one(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?,
+        };
+        let mut extra = match since {
+            Some(since) => sqlx::query_as::<_, RecordedPaymentRow>(
+                r#"
+                select tx_hash, amount_cents, status
+                from payments
+                where source = 'user'
+                  and status = 'settled'
+                  and created_at >= $1
+                  and not (tx_hash = any($2))
+                order by created_at
+                limit $3
+                "#,
+            )
+            .bind(since)
+            .bind(&tx_hashes)
+            .bind(MAX_RECONCILE_EXTRA as i64 + 1)
+            .fetch_all(&db)
+            .await
+            .map_err(ApiError::from_sqlx)?
+            .into_iter()
+            .map(RecordedPayment::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))?,
+            None => Vec::new(),
+        };
+        let extra_truncated = extra.len() > MAX_RECONCILE_EXTRA;
+        extra.truncate(MAX_RECONCILE_EXTRA);
+
+        let checked = settlements.len();
+        let mut missing = Vec::new();
+        let mut mismatched = Vec::new();
+        for entry in settlements {
+            match recorded.get(&entry.tx_hash) {
+                None => missing.push(entry),
+                Some(row)
+                    if row.amount_cents != entry.amount_cents
+                        || row.status != entry.status.as_db_str() =>
+                {
+                    mismatched.push(ReconcileMismatch {
+                        tx_hash: entry.tx_hash,
+                        reported_amount_cents: entry.amount_cents,
+                        reported_status: entry.status,
+                        recorded_amount_cents: row.amount_cents,
+                        recorded_status: row.status.clone(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok((
+            StatusCode::OK,
+            Json(ReconcileReport {
+                checked,
+                matched: checked - missing.len() - mismatched.len(),
+                missing,
+                mismatched,
+                extra,
+                extra_truncated,
+            }),
+        ))
+    }
+    .await;
+
+    respond(&metrics, "/admin/reconcile", result)
+}
+
+async fn prometheus_metrics(State(state): State<SharedState>, headers: HeaderMap) -> Response {
     let state = state.inner.read().await;
+
+    if let Some(expected) = state.config.metrics_bearer_token.as_deref() {
+        let authorized = bearer_token(&headers)
+            .is_some_and(|provided| constant_time_eq(provided.as_bytes(), expected.as_bytes()));
+        if !authorized {
+            return respond(
+                &state.metrics,
+                "/metrics",
+                Err::<Response, ApiError>(ApiError::unauthorized("metrics bearer token required")),
+            );
+        }
+    }
+
     let metric_families = state.metrics.registry.gather();
     let mut buffer = Vec::new();
     let encoder = TextEncoder::new();
@@ -2,52 +2,147 @@ use axum::http::StatusCode;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::time::{Duration, Instant};
 
 use crate::error::{ApiError, ApiResult};
-use crate::types::{AppConfig, X402PaymentRequirement, X402SettleResponse, X402VerifyResponse};
+use crate::types::{
+    AppConfig, DependencyStatus, FACILITATOR_PROBE_TIMEOUT_SECS, FacilitatorDialect, Metrics,
+    REQUEST_ID_HEADER, X402PaymentRequirement, X402SettleResponse, X402VerifyResponse,
+};
 
 #[derive(Debug, Clone)]
 pub struct VerifiedX402Payment {
     pub tx_hash: Option<String>,
-    pub payment_response_header: String,
+    pub payer: Option<String>,
+    /// `None` while settlement is still queued in async settle mode.
+    pub payment_response_header: Option<String>,
+    pub settlement_pending: bool,
+    pub network: String,
+    pub scheme: String,
+}
+
+/// Identity of a payment attempt as far as it can be read from the signed payload, used to
+/// record rejected attempts once per nonce.
+#[derive(Debug, Clone, Default)]
+pub struct X402PaymentAttempt {
+    pub payer: Option<String>,
+    pub nonce: Option<String>,
+}
+
+pub fn payment_attempt(payment_signature: &str) -> X402PaymentAttempt {
+    let Ok(payment_payload) = decode_payment_signature(payment_signature) else {
+        return X402PaymentAttempt::default();
+    };
+    let authorization = payment_payload
+        .get("payload")
+        .and_then(|payload| payload.get("authorization"));
+    let field = |key: &str| {
+        authorization
+            .and_then(|authorization| authorization.get(key))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    X402PaymentAttempt {
+        payer: field("from"),
+        nonce: field("nonce"),
+    }
 }
 
 pub async fn verify_and_settle_x402_payment(
     http: &reqwest::Client,
     config: &AppConfig,
+    metrics: &Metrics,
     payment_signature: &str,
     requirement: &X402PaymentRequirement,
+    request_id: &str,
 ) -> ApiResult<VerifiedX402Payment> {
+    let verify_response = verify_x402_signature(
+        http,
+        config,
+        metrics,
+        payment_signature,
+        requirement,
+        request_id,
+    )
+    .await?;
+    settle_x402_signature(
+        http,
+        config,
+        metrics,
+        payment_signature,
+        requirement,
+        request_id,
+        verify_response.payer,
+    )
+    .await
+}
+
+/// Verifies a payment with the facilitator without settling it.
+pub async fn verify_x402_signature(
+    http: &reqwest::Client,
+    config: &AppConfig,
+    metrics: &Metrics,
+    payment_signature: &str,
+    requirement: &X402PaymentRequirement,
+    request_id: &str,
+) -> ApiResult<X402VerifyResponse> {
     let payment_payload = decode_payment_signature(payment_signature)?;
+    validate_payment_payload(&payment_payload, requirement)?;
 
-    let verify_response: X402VerifyResponse = post_to_facilitator(
+    let started = Instant::now();
+    let verify_response = post_to_facilitator::<X402VerifyResponse>(
         http,
         config,
         &config.x402_verify_path,
+        payment_signature,
         &payment_payload,
         requirement,
+        request_id,
     )
-    .await?;
+    .await;
+    metrics.observe_facilitator_request("verify", started);
+    let verify_response = verify_response?;
 
     if !verify_response.is_valid {
-        return Err(ApiError::validation(
+        return Err(ApiError::payment_rejected(
             verify_response
                 .invalid_reason
                 .unwrap_or_else(|| "facilitator rejected payment signature".to_string()),
         ));
     }
 
-    let settle_response: X402SettleResponse = post_to_facilitator(
+    Ok(verify_response)
+}
+
+/// Settles an already verified payment; `verified_payer` fills in when the facilitator's
+/// settle response omits the payer.
+pub async fn settle_x402_signature(
+    http: &reqwest::Client,
+    config: &AppConfig,
+    metrics: &Metrics,
+    payment_signature: &str,
+    requirement: &X402PaymentRequirement,
+    request_id: &str,
+    verified_payer: Option<String>,
+) -> ApiResult<VerifiedX402Payment> {
+    let payment_payload = decode_payment_signature(payment_signature)?;
+    let started = Instant::now();
+    let settle_response = post_to_facilitator::<X402SettleResponse>(
         http,
         config,
         &config.x402_settle_path,
+        payment_signature,
         &payment_payload,
         requirement,
+        request_id,
     )
-    .await?;
+    .await;
+    metrics.observe_facilitator_request("settle", started);
+    let settle_response = settle_response?;
 
     if !settle_response.success {
-        return Err(ApiError::validation(
+        return Err(ApiError::payment_rejected(
             settle_response
                 .error_reason
                 .unwrap_or_else(|| "facilitator could not settle payment".to_string()),
@@ -62,10 +157,30 @@ pub async fn verify_and_settle_x402_payment(
 
     Ok(VerifiedX402Payment {
         tx_hash: settle_response.transaction,
-        payment_response_header,
+        payer: settle_response.payer.or(verified_payer),
+        payment_response_header: Some(payment_response_header),
+        settlement_pending: false,
+        network: requirement.network.clone(),
+        scheme: requirement.scheme.clone(),
     })
 }
 
+pub async fn probe_facilitator(http: &reqwest::Client, config: &AppConfig) -> DependencyStatus {
+    let response = http
+        .get(&config.x402_facilitator_url)
+        .timeout(Duration::from_secs(FACILITATOR_PROBE_TIMEOUT_SECS))
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_server_error() => {
+            DependencyStatus::down(format!("facilitator returned status={}", response.status()))
+        }
+        Ok(response) => DependencyStatus::up(Some(format!("status={}", response.status()))),
+        Err(err) => DependencyStatus::down(err.to_string()),
+    }
+}
+
 fn decode_payment_signature(payment_signature: &str) -> ApiResult<Value> {
     let decoded = STANDARD
         .decode(payment_signature)
@@ -78,21 +193,129 @@ fn decode_payment_signature(payment_signature: &str) -> ApiResult<Value> {
     })
 }
 
+/// Picks the challenge option the client signed for, matched on network and (when the payload
+/// echoes it) asset. With a single option, mismatches are left to `validate_payment_payload`
+/// so its specific messages still apply.
+pub fn select_payment_requirement<'a>(
+    payment_signature: &str,
+    requirements: &'a [X402PaymentRequirement],
+) -> ApiResult<&'a X402PaymentRequirement> {
+    let payment_payload = decode_payment_signature(payment_signature)?;
+    let (first, rest) = requirements
+        .split_first()
+        .ok_or_else(|| ApiError::config("no x402 assets configured"))?;
+    let Some(network) = payment_payload_field(&payment_payload, "network") else {
+        return Ok(first);
+    };
+    let asset = payment_payload_field(&payment_payload, "asset");
+
+    if let Some(requirement) = requirements.iter().find(|requirement| {
+        requirement.network == network
+            && asset.is_none_or(|asset| asset.eq_ignore_ascii_case(&requirement.asset))
+    }) {
+        return Ok(requirement);
+    }
+    if rest.is_empty() {
+        return Ok(first);
+    }
+
+    let accepted = requirements
+        .iter()
+        .map(|requirement| format!("{}/{}", requirement.network, requirement.asset))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(ApiError::validation(format!(
+        "PAYMENT-SIGNATURE pays in unsupported asset '{}' on network '{network}'; accepted: {accepted}",
+        asset.unwrap_or("unspecified")
+    )))
+}
+
+/// Checks a payload that decodes against `requirement` without contacting the facilitator.
+pub fn validate_payment_signature(
+    payment_signature: &str,
+    requirement: &X402PaymentRequirement,
+) -> ApiResult<()> {
+    validate_payment_payload(&decode_payment_signature(payment_signature)?, requirement)
+}
+
+/// Rejects structurally invalid payloads locally so they never cost a facilitator round-trip.
+/// x402 v2 payloads carry the chosen requirement under `accepted`; v1 payloads carry
+/// `scheme`/`network` at the top level.
+fn validate_payment_payload(
+    payment_payload: &Value,
+    requirement: &X402PaymentRequirement,
+) -> ApiResult<()> {
+    if !payment_payload.is_object() {
+        return Err(ApiError::validation(
+            "PAYMENT-SIGNATURE payload must be a JSON object",
+        ));
+    }
+
+    let scheme = payment_payload_field(payment_payload, "scheme").ok_or_else(|| {
+        ApiError::validation("PAYMENT-SIGNATURE payload is missing the scheme field")
+    })?;
+    let network = payment_payload_field(payment_payload, "network").ok_or_else(|| {
+        ApiError::validation("PAYMENT-SIGNATURE payload is missing the network field")
+    })?;
+
+    if network != requirement.network {
+        return Err(ApiError::validation(format!(
+            "PAYMENT-SIGNATURE network '{network}' does not match required network '{}'",
+            requirement.network
+        )));
+    }
+
+    if scheme != requirement.scheme {
+        return Err(ApiError::validation(format!(
+            "PAYMENT-SIGNATURE scheme '{scheme}' does not match required scheme '{}'",
+            requirement.scheme
+        )));
+    }
+
+    // Only v2 payloads echo the asset; v1 payloads leave it to the requirement.
+    if let Some(asset) = payment_payload_field(payment_payload, "asset")
+        && !asset.eq_ignore_ascii_case(&requirement.asset)
+    {
+        return Err(ApiError::validation(format!(
+            "PAYMENT-SIGNATURE asset '{asset}' does not match required asset '{}'",
+            requirement.asset
+        )));
+    }
+
+    Ok(())
+}
+
+fn payment_payload_field<'a>(payment_payload: &'a Value, key: &str) -> Option<&'a str> {
+    payment_payload
+        .get("accepted")
+        .and_then(|accepted| accepted.get(key))
+        .or_else(|| payment_payload.get(key))
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
 async fn post_to_facilitator<T: DeserializeOwned>(
     http: &reqwest::Client,
     config: &AppConfig,
     path: &str,
+    payment_signature: &str,
     payment_payload: &Value,
     requirement: &X402PaymentRequirement,
+    request_id: &str,
 ) -> ApiResult<T> {
-    let body = serde_json::json!({
-        "x402Version": 2,
-        "paymentPayload": payment_payload,
-        "paymentRequirements": requirement
-    });
-
-    let url = join_url(&config.x402_facilitator_url, path);
-    let mut request = http.post(&url).json(&body);
+    let body = facilitator_request_body(
+        config.x402_facilitator_dialect,
+        payment_signature,
+        payment_payload,
+        requirement,
+    );
+
+    let url = facilitator_endpoint(&config.x402_facilitator_url, path).map_err(ApiError::config)?;
+    let mut request = http
+        .post(url.clone())
+        .header(REQUEST_ID_HEADER, request_id)
+        .json(&body);
     if let Some(token) = config.x402_facilitator_bearer_token.as_deref() {
         request = request.bearer_auth(token);
     }
@@ -124,11 +347,52 @@ async fn post_to_facilitator<T: DeserializeOwned>(
     })
 }
 
-fn join_url(base: &str, path: &str) -> String {
-    let trimmed_base = base.trim_end_matches('/');
-    if path.starts_with('/') {
-        format!("{trimmed_base}{path}")
-    } else {
-        format!("{trimmed_base}/{path}")
+pub fn facilitator_request_body(
+    dialect: FacilitatorDialect,
+    payment_signature: &str,
+    payment_payload: &Value,
+    requirement: &X402PaymentRequirement,
+) -> Value {
+    match dialect {
+        FacilitatorDialect::X402 => serde_json::json!({
+            "x402Version": 2,
+            "paymentPayload": payment_payload,
+            "paymentRequirements": requirement
+        }),
+        FacilitatorDialect::X402V1 => serde_json::json!({
+            "x402Version": 1,
+            "paymentHeader": payment_signature,
+            "paymentRequirements": requirement
+        }),
+        FacilitatorDialect::Compact => serde_json::json!({
+            "payment": payment_payload,
+            "requirements": requirement
+        }),
+    }
+}
+
+/// Joins a facilitator path onto the base URL with exactly one slash between them. Any path
+/// prefix on the base is kept, so `https://x402.org/facilitator` with `verify` or `/verify`
+/// both become `https://x402.org/facilitator/verify`.
+pub fn facilitator_endpoint(base: &str, path: &str) -> Result<reqwest::Url, String> {
+    let base_url = reqwest::Url::parse(&format!("{}/", base.trim().trim_end_matches('/')))
+        .map_err(|err| format!("invalid X402_FACILITATOR_URL '{base}': {err}"))?;
+    if !matches!(base_url.scheme(), "http" | "https") {
+        return Err(format!("X402_FACILITATOR_URL '{base}' must be http(s)"));
+    }
+
+    let relative = path.trim().trim_start_matches('/');
+    if relative.is_empty() {
+        return Err(format!("facilitator path '{path}' must not be empty"));
+    }
+    let url = base_url
+        .join(relative)
+        .map_err(|err| format!("invalid facilitator path '{path}': {err}"))?;
+    // `join` resolves `..` segments and absolute URLs, which would escape the base.
+    if !url.as_str().starts_with(base_url.as_str()) {
+        return Err(format!(
+            "facilitator path '{path}' must stay under X402_FACILITATOR_URL '{base}'"
+        ));
     }
+    Ok(url)
 }
@@ -0,0 +1,26 @@
+//! JSON Schema checks for sponsored API contracts, backed by the `jsonschema` crate.
+//!
+//! Schemas follow the draft named by their `$schema`, defaulting to 2020-12. Remote and file
+//! `$ref`s are not fetched, so a schema that needs one is rejected when it is checked.
+
+use serde_json::Value;
+
+/// Checks that `schema` is a valid JSON Schema that can be compiled.
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    jsonschema::validator_for(schema)
+        .map(|_| ())
+        .map_err(|err| format!("#{}: {err}", err.instance_path()))
+}
+
+/// Validates `instance` against a schema accepted by `check_schema`, returning one message per
+/// violation prefixed with the JSON pointer of the offending value (empty for the root).
+pub fn validate(schema: &Value, instance: &Value) -> Vec<String> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(err) => return vec![format!(": schema is invalid: {err}")],
+    };
+    validator
+        .iter_errors(instance)
+        .map(|err| format!("{}: {err}", err.instance_path()))
+        .collect()
+}
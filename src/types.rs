@@ -1,53 +1,212 @@
+use axum::http::{HeaderName, HeaderValue, StatusCode};
 use chrono::{DateTime, Utc};
-use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+use prometheus::{
+    GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::{collections::HashMap, sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{RwLock, mpsc::Sender};
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::{onchain::facilitator_endpoint, schema::check_schema, utils::is_public_ip};
+
 pub const PAYMENT_SIGNATURE_HEADER: &str = "payment-signature";
 pub const PAYMENT_REQUIRED_HEADER: &str = "payment-required";
 pub const PAYMENT_RESPONSE_HEADER: &str = "payment-response";
 pub const X402_VERSION_HEADER: &str = "x402-version";
+/// Connection-scoped headers that must never be copied from a caller to a sponsored upstream.
+pub const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+pub const PAYMENT_MODE_HEADER: &str = "x-payment-mode";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Set on list responses that left out rows the server could not read.
+pub const SKIPPED_ROWS_HEADER: &str = "x-skipped-rows";
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+pub const MAX_CALLER_LEN: usize = 128;
 pub const DEFAULT_PRICE_CENTS: u64 = 5;
+pub const DEFAULT_MIN_PRICE_CENTS: u64 = 1;
+pub const DEFAULT_MAX_PRICE_CENTS: u64 = 100_000;
 pub const SPONSORED_API_CREATE_SERVICE: &str = "sponsored-api-create";
 pub const SPONSORED_API_SERVICE_PREFIX: &str = "sponsored-api";
+/// Internal services priced by the platform itself; never runnable via `/tool` or `/proxy`.
+pub const RESERVED_SERVICES: &[&str] = &[SPONSORED_API_CREATE_SERVICE];
 pub const DEFAULT_SPONSORED_API_CREATE_PRICE_CENTS: u64 = 25;
 pub const DEFAULT_SPONSORED_API_TIMEOUT_SECS: u64 = 12;
+pub const DEFAULT_SPONSORED_API_MAX_TIMEOUT_SECS: u64 = 25;
+pub const DEFAULT_MAX_UPSTREAM_HEADERS: u64 = 50;
+pub const DEFAULT_MAX_UPSTREAM_HEADERS_BYTES: u64 = 8 * 1024;
 pub const DEFAULT_X402_FACILITATOR_URL: &str = "https://x402.org/facilitator";
 pub const DEFAULT_X402_VERIFY_PATH: &str = "/verify";
 pub const DEFAULT_X402_SETTLE_PATH: &str = "/settle";
 pub const DEFAULT_X402_NETWORK: &str = "base-sepolia";
+pub const DEFAULT_X402_SCHEME: &str = "exact";
+/// Decimals of the primary `X402_ASSET` (USDC).
+pub const DEFAULT_X402_ASSET_DECIMALS: u32 = 6;
+/// Upper bound on asset decimals so cent amounts always fit in `u128` base units.
+pub const MAX_X402_ASSET_DECIMALS: u32 = 18;
 pub const DEFAULT_PUBLIC_BASE_URL: &str = "http://localhost:3000";
+/// Payer and `tx_hash` prefix of payments accepted under `X402_TEST_MODE`, so they are easy to
+/// find and purge.
+pub const TEST_MODE_PAYER: &str = "0x000000000000000000000000000000000000dEaD";
+pub const TEST_MODE_TX_HASH_PREFIX: &str = "test-";
+pub const DEFAULT_TOP_SPONSORS_LIMIT: i64 = 10;
+pub const MAX_TOP_SPONSORS_LIMIT: i64 = 100;
+pub const MAX_RECONCILE_ENTRIES: usize = 1000;
+pub const MAX_RECONCILE_EXTRA: usize = 1000;
+pub const MAX_SETTLEMENT_BATCH: usize = 1000;
+pub const MAX_PROFILE_BATCH: usize = 100;
+pub const DEFAULT_SPONSORED_API_CALLS_LIMIT: i64 = 50;
+pub const MAX_SPONSORED_API_CALLS_LIMIT: i64 = 200;
+pub const DEFAULT_TASK_COMPLETIONS_LIMIT: i64 = 50;
+pub const MAX_TASK_COMPLETIONS_LIMIT: i64 = 200;
+pub const DEFAULT_ELIGIBLE_USERS_SAMPLE: i64 = 10;
+pub const MAX_ELIGIBLE_USERS_SAMPLE: i64 = 100;
+pub const DEFAULT_AUDIT_LOG_LIMIT: i64 = 100;
+pub const MAX_AUDIT_LOG_LIMIT: i64 = 500;
+pub const FACILITATOR_PROBE_TIMEOUT_SECS: u64 = 2;
+pub const FACILITATOR_HEALTH_CACHE_SECS: u64 = 15;
+pub const DB_RETRY_AFTER_SECS: u64 = 1;
+pub const BUDGET_WEBHOOK_TIMEOUT_SECS: u64 = 5;
+pub const BUDGET_WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+pub const SPONSORED_CALL_LOG_MAX_ATTEMPTS: u32 = 3;
+/// How long a sponsored proxy run's `Idempotency-Key` replays the first run. After that the
+/// key is refused, so a client cannot keep one key alive as a standing free pass.
+pub const SPONSORED_IDEMPOTENCY_KEY_TTL_SECS: i64 = 24 * 60 * 60;
+pub const DEFAULT_X402_VERSION: &str = "2";
+pub const HTTP_CLIENT_TIMEOUT_SECS: u64 = 20;
+/// reqwest's own defaults, kept so an unset env var changes nothing.
+pub const DEFAULT_HTTP_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+pub const DEFAULT_HTTP_TCP_KEEPALIVE_SECS: u64 = 15;
+pub const DEFAULT_FACILITATOR_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_FACILITATOR_POOL_MAX_IDLE_PER_HOST: u64 = 8;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_SPONSORED_API_MAX_REDIRECTS: u64 = 5;
+pub const DEFAULT_X402_RECONCILE_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_X402_RECONCILE_AFTER_SECS: u64 = 300;
+pub const DEFAULT_X402_SETTLE_QUEUE_CAPACITY: u64 = 1000;
+/// Settle calls a pending payment gets, counting the worker's own, before it is recorded as
+/// failed.
+pub const MAX_SETTLE_ATTEMPTS: i32 = 5;
+/// Pending payments one reconciliation pass retries.
+pub const RECONCILE_BATCH_SIZE: i64 = 100;
 
 #[derive(Clone)]
 pub struct AppConfig {
+    pub default_price_cents: u64,
+    /// Inclusive bounds for sponsored API `price_cents` and campaign `subsidy_per_call_cents`.
+    pub min_price_cents: u64,
+    pub max_price_cents: u64,
     pub sponsored_api_create_price_cents: u64,
     pub sponsored_api_timeout_secs: u64,
+    /// Upper bound for a run's own `timeout_secs`; never below `sponsored_api_timeout_secs`.
+    pub sponsored_api_max_timeout_secs: u64,
+    pub max_upstream_headers: u64,
+    pub max_upstream_headers_bytes: u64,
+    /// Redirects followed for a sponsored API with `follow_redirects`; others follow none.
+    pub sponsored_api_max_redirects: u64,
     pub x402_facilitator_url: String,
     pub x402_verify_path: String,
     pub x402_settle_path: String,
     pub x402_facilitator_bearer_token: Option<String>,
     pub x402_network: String,
+    pub x402_scheme: X402Scheme,
+    /// Sent as the `x402-version` header and `x402_version` challenge field.
+    pub x402_version: String,
+    pub x402_settle_mode: SettleMode,
+    /// How often the async settle mode reconciler looks for stuck `pending` payments.
+    pub x402_reconcile_interval_secs: u64,
+    /// Age at which a `pending` payment counts as stuck and its settle call is retried.
+    pub x402_reconcile_after_secs: u64,
+    /// Settlements the async worker may have queued; once full, payments settle in the request.
+    pub x402_settle_queue_capacity: u64,
+    /// Header names the facilitator's settlement response is returned under, in order.
+    pub payment_response_headers: Vec<HeaderName>,
+    pub x402_facilitator_dialect: FacilitatorDialect,
     pub x402_pay_to: Option<String>,
     pub x402_asset: Option<String>,
+    /// Further assets offered next to `X402_ASSET` in every challenge.
+    pub x402_extra_assets: Vec<X402AcceptedAsset>,
+    /// `outputSchema` advertised in the payment requirements of each listed service.
+    pub x402_output_schemas: HashMap<String, Value>,
     pub public_base_url: String,
+    pub management_api_key: Option<String>,
+    pub metrics_bearer_token: Option<String>,
+    /// Uppercased region codes profiles may use; `None` accepts any region.
+    pub allowed_regions: Option<Vec<String>>,
+    /// Lowercased email domains new profiles may not use; subdomains are blocked too.
+    pub blocked_email_domains: Vec<String>,
+    /// Idle upstream/facilitator connections kept per host; `None` keeps any number.
+    pub http_pool_max_idle_per_host: Option<u64>,
+    pub http_pool_idle_timeout_secs: u64,
+    /// `0` leaves `SO_KEEPALIVE` unset.
+    pub http_tcp_keepalive_secs: u64,
+    pub facilitator_timeout_secs: u64,
+    pub facilitator_pool_max_idle_per_host: u64,
+    /// Used for campaigns created without their own `incomplete_task_behavior`.
+    pub incomplete_task_behavior: IncompleteTaskBehavior,
+    /// Deadline for a whole API request; always longer than any single outbound call.
+    pub request_timeout_secs: u64,
+    /// Smallest challenge amount, in the asset's base units, that a price may convert to;
+    /// `0` sets no floor.
+    pub min_charge_base_units: u64,
+    /// Accepts any non-empty `PAYMENT-SIGNATURE` without calling the facilitator. Never enabled
+    /// for a production-looking `public_base_url`.
+    pub x402_test_mode: bool,
 }
 
 impl AppConfig {
     pub fn from_env() -> Self {
-        Self {
+        let mut config = Self {
+            default_price_cents: read_env_u64("DEFAULT_PRICE_CENTS", DEFAULT_PRICE_CENTS),
+            // A zero price would make the x402 challenge amount zero, so the floor is at least 1.
+            min_price_cents: read_env_u64("MIN_PRICE_CENTS", DEFAULT_MIN_PRICE_CENTS).max(1),
+            max_price_cents: read_env_u64("MAX_PRICE_CENTS", DEFAULT_MAX_PRICE_CENTS),
             sponsored_api_create_price_cents: read_env_u64(
                 "SPONSORED_API_CREATE_PRICE_CENTS",
                 DEFAULT_SPONSORED_API_CREATE_PRICE_CENTS,
             ),
-            sponsored_api_timeout_secs: read_env_u64(
+            sponsored_api_timeout_secs: read_env_nonzero_u64(
                 "SPONSORED_API_TIMEOUT_SECS",
                 DEFAULT_SPONSORED_API_TIMEOUT_SECS,
             ),
+            sponsored_api_max_timeout_secs: read_env_nonzero_u64(
+                "SPONSORED_API_MAX_TIMEOUT_SECS",
+                DEFAULT_SPONSORED_API_MAX_TIMEOUT_SECS,
+            ),
+            max_upstream_headers: read_env_u64(
+                "SPONSORED_API_MAX_UPSTREAM_HEADERS",
+                DEFAULT_MAX_UPSTREAM_HEADERS,
+            ),
+            max_upstream_headers_bytes: read_env_u64(
+                "SPONSORED_API_MAX_UPSTREAM_HEADERS_BYTES",
+                DEFAULT_MAX_UPSTREAM_HEADERS_BYTES,
+            ),
+            sponsored_api_max_redirects: read_env_u64(
+                "SPONSORED_API_MAX_REDIRECTS",
+                DEFAULT_SPONSORED_API_MAX_REDIRECTS,
+            ),
             x402_facilitator_url: std::env::var("X402_FACILITATOR_URL")
                 .unwrap_or_else(|_| DEFAULT_X402_FACILITATOR_URL.to_string()),
             x402_verify_path: std::env::var("X402_VERIFY_PATH")
@@ -57,14 +216,524 @@ impl AppConfig {
             x402_facilitator_bearer_token: std::env::var("X402_FACILITATOR_BEARER_TOKEN").ok(),
             x402_network: std::env::var("X402_NETWORK")
                 .unwrap_or_else(|_| DEFAULT_X402_NETWORK.to_string()),
+            x402_scheme: std::env::var("X402_SCHEME")
+                .map(|value| {
+                    X402Scheme::parse(&value).unwrap_or_else(|| {
+                        panic!("unsupported X402_SCHEME '{value}'; expected exact or upto")
+                    })
+                })
+                .unwrap_or_default(),
+            x402_version: std::env::var("X402_VERSION")
+                .map(|value| {
+                    let value = value.trim().to_string();
+                    if value.is_empty() || HeaderValue::from_str(&value).is_err() {
+                        panic!("X402_VERSION '{value}' is not a valid header value");
+                    }
+                    value
+                })
+                .unwrap_or_else(|_| DEFAULT_X402_VERSION.to_string()),
+            x402_settle_mode: std::env::var("X402_SETTLE_MODE")
+                .map(|value| SettleMode::from_env_value(&value))
+                .unwrap_or_default(),
+            x402_reconcile_interval_secs: read_env_nonzero_u64(
+                "X402_RECONCILE_INTERVAL_SECS",
+                DEFAULT_X402_RECONCILE_INTERVAL_SECS,
+            ),
+            x402_reconcile_after_secs: read_env_nonzero_u64(
+                "X402_RECONCILE_AFTER_SECS",
+                DEFAULT_X402_RECONCILE_AFTER_SECS,
+            ),
+            x402_settle_queue_capacity: read_env_nonzero_u64(
+                "X402_SETTLE_QUEUE_CAPACITY",
+                DEFAULT_X402_SETTLE_QUEUE_CAPACITY,
+            ),
+            payment_response_headers: read_payment_response_headers(),
+            x402_facilitator_dialect: std::env::var("X402_FACILITATOR_DIALECT")
+                .map(|value| FacilitatorDialect::from_env_value(&value))
+                .unwrap_or_default(),
             x402_pay_to: std::env::var("X402_PAY_TO").ok(),
             x402_asset: std::env::var("X402_ASSET").ok(),
+            x402_extra_assets: std::env::var("X402_EXTRA_ASSETS")
+                .map(|value| X402AcceptedAsset::parse_list(&value))
+                .unwrap_or_default(),
+            x402_output_schemas: std::env::var("SERVICE_OUTPUT_SCHEMAS")
+                .map(|value| parse_service_output_schemas(&value))
+                .unwrap_or_default(),
             public_base_url: std::env::var("PUBLIC_BASE_URL")
                 .unwrap_or_else(|_| DEFAULT_PUBLIC_BASE_URL.to_string()),
+            management_api_key: std::env::var("MANAGEMENT_API_KEY")
+                .ok()
+                .filter(|value| !value.trim().is_empty()),
+            metrics_bearer_token: std::env::var("METRICS_BEARER_TOKEN")
+                .ok()
+                .filter(|value| !value.trim().is_empty()),
+            allowed_regions: std::env::var("ALLOWED_REGIONS")
+                .ok()
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|region| region.trim().to_ascii_uppercase())
+                        .filter(|region| !region.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|regions| !regions.is_empty()),
+            blocked_email_domains: std::env::var("BLOCKED_EMAIL_DOMAINS")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|domain| domain.trim().trim_start_matches('@').to_ascii_lowercase())
+                        .filter(|domain| !domain.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            http_pool_max_idle_per_host: std::env::var("HTTP_POOL_MAX_IDLE_PER_HOST")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok()),
+            http_pool_idle_timeout_secs: read_env_nonzero_u64(
+                "HTTP_POOL_IDLE_TIMEOUT_SECS",
+                DEFAULT_HTTP_POOL_IDLE_TIMEOUT_SECS,
+            ),
+            http_tcp_keepalive_secs: read_env_u64(
+                "HTTP_TCP_KEEPALIVE_SECS",
+                DEFAULT_HTTP_TCP_KEEPALIVE_SECS,
+            ),
+            facilitator_timeout_secs: read_env_nonzero_u64(
+                "FACILITATOR_TIMEOUT_SECS",
+                DEFAULT_FACILITATOR_TIMEOUT_SECS,
+            ),
+            facilitator_pool_max_idle_per_host: read_env_u64(
+                "FACILITATOR_POOL_MAX_IDLE_PER_HOST",
+                DEFAULT_FACILITATOR_POOL_MAX_IDLE_PER_HOST,
+            ),
+            incomplete_task_behavior: std::env::var("INCOMPLETE_TASK_BEHAVIOR")
+                .map(|value| {
+                    IncompleteTaskBehavior::parse(&value).unwrap_or_else(|| {
+                        panic!(
+                            "unsupported INCOMPLETE_TASK_BEHAVIOR '{value}'; expected \
+                             require_task_or_pay or skip_if_incomplete"
+                        )
+                    })
+                })
+                .unwrap_or_default(),
+            request_timeout_secs: read_env_nonzero_u64(
+                "REQUEST_TIMEOUT_SECS",
+                DEFAULT_REQUEST_TIMEOUT_SECS,
+            ),
+            min_charge_base_units: read_env_u64("MIN_CHARGE_BASE_UNITS", 0),
+            x402_test_mode: read_env_bool("X402_TEST_MODE"),
+        };
+        // The default timeout is always an allowed per-call value.
+        config.sponsored_api_max_timeout_secs = config
+            .sponsored_api_max_timeout_secs
+            .max(config.sponsored_api_timeout_secs);
+
+        // Otherwise every price, including the default, would be rejected.
+        if config.min_price_cents > config.max_price_cents {
+            panic!(
+                "MIN_PRICE_CENTS ({}) must not exceed MAX_PRICE_CENTS ({})",
+                config.min_price_cents, config.max_price_cents
+            );
+        }
+
+        // A deadline no longer than the outbound calls of one run would cut off runs that are
+        // merely slow.
+        let slowest_run = slowest_paid_run_secs(
+            config.facilitator_timeout_secs,
+            config.sponsored_api_max_timeout_secs,
+        );
+        if config.request_timeout_secs <= slowest_run {
+            panic!(
+                "REQUEST_TIMEOUT_SECS ({}) must be longer than a facilitator verify and settle \
+                 plus SPONSORED_API_MAX_TIMEOUT_SECS ({slowest_run})",
+                config.request_timeout_secs
+            );
+        }
+
+        if config.x402_test_mode && looks_like_production_url(&config.public_base_url) {
+            panic!(
+                "X402_TEST_MODE would accept unpaid calls, but PUBLIC_BASE_URL ({}) looks like \
+                 production; use a localhost, .local, .test, or staging/dev host",
+                config.public_base_url
+            );
+        }
+
+        // Fail at startup rather than on the first paid call.
+        for path in [&config.x402_verify_path, &config.x402_settle_path] {
+            if let Err(err) = facilitator_endpoint(&config.x402_facilitator_url, path) {
+                panic!("{err}");
+            }
+        }
+        config
+    }
+
+    /// The client for sponsored API upstreams and budget webhooks. It never follows redirects
+    /// itself, since a redirect could lead to an internal address; `send_upstream` follows them
+    /// hop by hop for APIs that opt in.
+    pub fn http_client(&self) -> Client {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(HTTP_CLIENT_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none())
+            .pool_idle_timeout(Duration::from_secs(self.http_pool_idle_timeout_secs))
+            .tcp_keepalive(
+                (self.http_tcp_keepalive_secs > 0)
+                    .then(|| Duration::from_secs(self.http_tcp_keepalive_secs)),
+            );
+        if let Some(max_idle) = self.http_pool_max_idle_per_host {
+            builder =
+                builder.pool_max_idle_per_host(usize::try_from(max_idle).unwrap_or(usize::MAX));
+        }
+        builder.build().expect("http client should build")
+    }
+
+    /// The facilitator client: its own pool and a shorter timeout than upstream calls.
+    pub fn facilitator_http_client(&self) -> Client {
+        Client::builder()
+            .timeout(Duration::from_secs(self.facilitator_timeout_secs))
+            .pool_idle_timeout(Duration::from_secs(self.http_pool_idle_timeout_secs))
+            .pool_max_idle_per_host(
+                usize::try_from(self.facilitator_pool_max_idle_per_host).unwrap_or(usize::MAX),
+            )
+            .tcp_keepalive(
+                (self.http_tcp_keepalive_secs > 0)
+                    .then(|| Duration::from_secs(self.http_tcp_keepalive_secs)),
+            )
+            .build()
+            .expect("facilitator http client should build")
+    }
+}
+
+/// An asset accepted for x402 payments besides the primary `X402_ASSET`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct X402AcceptedAsset {
+    pub network: String,
+    pub asset: String,
+    pub decimals: u32,
+    /// Receiving wallet; `None` falls back to `X402_PAY_TO`.
+    pub pay_to: Option<String>,
+}
+
+impl X402AcceptedAsset {
+    /// Parses `X402_EXTRA_ASSETS`: comma-separated `network:asset:decimals[:pay_to]` entries.
+    /// Malformed entries are logged and skipped.
+    pub fn parse_list(value: &str) -> Vec<Self> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let parsed = Self::parse(entry);
+                if parsed.is_none() {
+                    tracing::warn!("ignoring malformed X402_EXTRA_ASSETS entry '{entry}'");
+                }
+                parsed
+            })
+            .collect()
+    }
+
+    fn parse(entry: &str) -> Option<Self> {
+        let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+        let (network, asset, decimals, pay_to) = match parts.as_slice() {
+            [network, asset, decimals] => (*network, *asset, *decimals, None),
+            [network, asset, decimals, pay_to] => (*network, *asset, *decimals, Some(*pay_to)),
+            _ => return None,
+        };
+        // Fewer than 2 decimals cannot express a cent.
+        let decimals = decimals
+            .parse::<u32>()
+            .ok()
+            .filter(|decimals| (2..=MAX_X402_ASSET_DECIMALS).contains(decimals))?;
+        if network.is_empty() || asset.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            network: network.to_string(),
+            asset: asset.to_string(),
+            decimals,
+            pay_to: pay_to
+                .filter(|pay_to| !pay_to.is_empty())
+                .map(str::to_string),
+        })
+    }
+}
+
+/// A configured secret as reported by `GET /admin/config`: only whether it is set.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SecretStatus {
+    pub present: bool,
+}
+
+impl SecretStatus {
+    fn of(secret: &Option<String>) -> Self {
+        Self {
+            present: secret.is_some(),
+        }
+    }
+}
+
+/// `AppConfig` with every secret replaced by a `SecretStatus`.
+#[derive(Debug, Serialize)]
+pub struct ConfigSnapshot {
+    pub default_price_cents: u64,
+    pub min_price_cents: u64,
+    pub max_price_cents: u64,
+    pub sponsored_api_create_price_cents: u64,
+    pub sponsored_api_timeout_secs: u64,
+    pub sponsored_api_max_timeout_secs: u64,
+    pub max_upstream_headers: u64,
+    pub max_upstream_headers_bytes: u64,
+    pub sponsored_api_max_redirects: u64,
+    pub x402_facilitator_url: String,
+    pub x402_verify_path: String,
+    pub x402_settle_path: String,
+    pub x402_facilitator_bearer_token: SecretStatus,
+    pub x402_network: String,
+    pub x402_scheme: X402Scheme,
+    pub x402_version: String,
+    pub x402_settle_mode: SettleMode,
+    pub x402_reconcile_interval_secs: u64,
+    pub x402_reconcile_after_secs: u64,
+    pub x402_settle_queue_capacity: u64,
+    pub payment_response_headers: Vec<String>,
+    pub x402_facilitator_dialect: FacilitatorDialect,
+    pub x402_pay_to: Option<String>,
+    pub x402_asset: Option<String>,
+    pub x402_extra_assets: Vec<X402AcceptedAsset>,
+    pub x402_output_schemas: HashMap<String, Value>,
+    pub public_base_url: String,
+    pub management_api_key: SecretStatus,
+    pub metrics_bearer_token: SecretStatus,
+    pub allowed_regions: Option<Vec<String>>,
+    pub blocked_email_domains: Vec<String>,
+    pub http_pool_max_idle_per_host: Option<u64>,
+    pub http_pool_idle_timeout_secs: u64,
+    pub http_tcp_keepalive_secs: u64,
+    pub facilitator_timeout_secs: u64,
+    pub facilitator_pool_max_idle_per_host: u64,
+    pub incomplete_task_behavior: IncompleteTaskBehavior,
+    pub request_timeout_secs: u64,
+    pub min_charge_base_units: u64,
+    pub x402_test_mode: bool,
+}
+
+impl From<&AppConfig> for ConfigSnapshot {
+    fn from(config: &AppConfig) -> Self {
+        // Destructured so a new `AppConfig` field fails to compile until it is classified
+        // here as public or secret.
+        let AppConfig {
+            default_price_cents,
+            min_price_cents,
+            max_price_cents,
+            sponsored_api_create_price_cents,
+            sponsored_api_timeout_secs,
+            sponsored_api_max_timeout_secs,
+            max_upstream_headers,
+            max_upstream_headers_bytes,
+            sponsored_api_max_redirects,
+            x402_facilitator_url,
+            x402_verify_path,
+            x402_settle_path,
+            x402_facilitator_bearer_token,
+            x402_network,
+            x402_scheme,
+            x402_version,
+            x402_settle_mode,
+            x402_reconcile_interval_secs,
+            x402_reconcile_after_secs,
+            x402_settle_queue_capacity,
+            payment_response_headers,
+            x402_facilitator_dialect,
+            x402_pay_to,
+            x402_asset,
+            x402_extra_assets,
+            x402_output_schemas,
+            public_base_url,
+            management_api_key,
+            metrics_bearer_token,
+            allowed_regions,
+            blocked_email_domains,
+            http_pool_max_idle_per_host,
+            http_pool_idle_timeout_secs,
+            http_tcp_keepalive_secs,
+            facilitator_timeout_secs,
+            facilitator_pool_max_idle_per_host,
+            incomplete_task_behavior,
+            request_timeout_secs,
+            min_charge_base_units,
+            x402_test_mode,
+        } = config;
+
+        Self {
+            default_price_cents: *default_price_cents,
+            min_price_cents: *min_price_cents,
+            max_price_cents: *max_price_cents,
+            sponsored_api_create_price_cents: *sponsored_api_create_price_cents,
+            sponsored_api_timeout_secs: *sponsored_api_timeout_secs,
+            sponsored_api_max_timeout_secs: *sponsored_api_max_timeout_secs,
+            max_upstream_headers: *max_upstream_headers,
+            max_upstream_headers_bytes: *max_upstream_headers_bytes,
+            sponsored_api_max_redirects: *sponsored_api_max_redirects,
+            x402_facilitator_url: x402_facilitator_url.clone(),
+            x402_verify_path: x402_verify_path.clone(),
+            x402_settle_path: x402_settle_path.clone(),
+            x402_facilitator_bearer_token: SecretStatus::of(x402_facilitator_bearer_token),
+            x402_network: x402_network.clone(),
+            x402_scheme: *x402_scheme,
+            x402_version: x402_version.clone(),
+            x402_settle_mode: *x402_settle_mode,
+            x402_reconcile_interval_secs: *x402_reconcile_interval_secs,
+            x402_reconcile_after_secs: *x402_reconcile_after_secs,
+            x402_settle_queue_capacity: *x402_settle_queue_capacity,
+            payment_response_headers: payment_response_headers
+                .iter()
+                .map(|name| name.as_str().to_string())
+                .collect(),
+            x402_facilitator_dialect: *x402_facilitator_dialect,
+            x402_pay_to: x402_pay_to.clone(),
+            x402_asset: x402_asset.clone(),
+            x402_extra_assets: x402_extra_assets.clone(),
+            x402_output_schemas: x402_output_schemas.clone(),
+            public_base_url: public_base_url.clone(),
+            management_api_key: SecretStatus::of(management_api_key),
+            metrics_bearer_token: SecretStatus::of(metrics_bearer_token),
+            allowed_regions: allowed_regions.clone(),
+            blocked_email_domains: blocked_email_domains.clone(),
+            http_pool_max_idle_per_host: *http_pool_max_idle_per_host,
+            http_pool_idle_timeout_secs: *http_pool_idle_timeout_secs,
+            http_tcp_keepalive_secs: *http_tcp_keepalive_secs,
+            facilitator_timeout_secs: *facilitator_timeout_secs,
+            facilitator_pool_max_idle_per_host: *facilitator_pool_max_idle_per_host,
+            incomplete_task_behavior: *incomplete_task_behavior,
+            request_timeout_secs: *request_timeout_secs,
+            min_charge_base_units: *min_charge_base_units,
+            x402_test_mode: *x402_test_mode,
+        }
+    }
+}
+
+/// Whether facilitator settlement happens inside the request (`sync`) or on the background
+/// settlement worker after verification (`async`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettleMode {
+    #[default]
+    Sync,
+    Async,
+}
+
+impl SettleMode {
+    pub fn from_env_value(value: &str) -> Self {
+        if value.trim().eq_ignore_ascii_case("async") {
+            Self::Async
+        } else {
+            Self::Sync
+        }
+    }
+}
+
+/// x402 payment scheme offered in challenges: `exact` charges the listed amount, `upto` lets
+/// the facilitator settle up to it for metered billing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum X402Scheme {
+    #[default]
+    Exact,
+    Upto,
+}
+
+impl X402Scheme {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "exact" => Some(Self::Exact),
+            "upto" => Some(Self::Upto),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Upto => "upto",
+        }
+    }
+}
+
+/// What `/proxy/{service}/run` does when a user matches a campaign but has not completed its
+/// `required_task`. `RequireTaskOrPay` answers `428` naming the task; `SkipIfIncomplete`
+/// ignores the campaign, so another campaign may sponsor the call or the caller gets the `402`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncompleteTaskBehavior {
+    #[default]
+    RequireTaskOrPay,
+    SkipIfIncomplete,
+}
+
+impl IncompleteTaskBehavior {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "require_task_or_pay" => Some(Self::RequireTaskOrPay),
+            "skip_if_incomplete" => Some(Self::SkipIfIncomplete),
+            _ => None,
+        }
+    }
+
+    /// Value stored in `campaigns.incomplete_task_behavior`.
+    pub fn as_db_str(self) -> &'static str {
+        match self {
+            Self::RequireTaskOrPay => "require_task_or_pay",
+            Self::SkipIfIncomplete => "skip_if_incomplete",
+        }
+    }
+}
+
+/// Request body shape posted to the facilitator's verify and settle endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum FacilitatorDialect {
+    /// `x402`: `{x402Version: 2, paymentPayload, paymentRequirements}`.
+    #[default]
+    #[serde(rename = "x402")]
+    X402,
+    /// `x402-v1`: `{x402Version: 1, paymentHeader, paymentRequirements}` with the raw
+    /// base64 signature, as older facilitators expect.
+    #[serde(rename = "x402-v1")]
+    X402V1,
+    /// `compact`: `{payment, requirements}`, used by some self-hosted facilitators.
+    #[serde(rename = "compact")]
+    Compact,
+}
+
+impl FacilitatorDialect {
+    pub fn from_env_value(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "x402-v1" => Self::X402V1,
+            "compact" => Self::Compact,
+            _ => Self::X402,
         }
     }
 }
 
+/// Who a direct payment is recorded against. Only paths that keep `payments` rows for direct
+/// payments pass one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PaymentAttribution {
+    pub user_id: Option<Uuid>,
+    pub caller: Option<String>,
+}
+
+/// A verified payment waiting for the background worker to settle it. Also stored on its
+/// `pending` payments row so the reconciler can retry it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettlementJob {
+    pub payment_signature: String,
+    pub requirement: X402PaymentRequirement,
+    pub service: String,
+    pub amount_cents: u64,
+    pub attribution: Option<PaymentAttribution>,
+    pub verified_payer: Option<String>,
+    pub request_id: String,
+}
+
 #[derive(Clone)]
 pub struct SharedState {
     pub inner: Arc<RwLock<AppState>>,
@@ -73,8 +742,13 @@ pub struct SharedState {
 pub struct AppState {
     pub metrics: Metrics,
     pub db: Option<PgPool>,
+    /// For untrusted destinations: sponsored API upstreams and budget webhooks.
     pub http: Client,
+    /// For the x402 facilitator only, so slow upstreams cannot starve settlement.
+    pub facilitator_http: Client,
     pub config: AppConfig,
+    pub facilitator_health: Option<(Instant, DependencyStatus)>,
+    pub settle_queue: Option<Sender<SettlementJob>>,
 }
 
 #[derive(Clone)]
@@ -82,8 +756,17 @@ pub struct Metrics {
     pub registry: Registry,
     pub http_requests_total: IntCounterVec,
     pub payment_events_total: IntCounterVec,
+    pub payment_challenges_total: IntCounterVec,
     pub creator_events_total: IntCounterVec,
     pub sponsor_spend_cents_total: IntCounter,
+    pub settle_queue_depth: IntGauge,
+    pub pending_settlements: IntGauge,
+    pub settlement_reconciliations_total: IntCounterVec,
+    pub db_pool_timeouts_total: IntCounter,
+    pub budget_utilization: GaugeVec,
+    pub budget_webhook_attempts_total: IntCounterVec,
+    pub sponsored_call_logs_dropped_total: IntCounter,
+    pub facilitator_request_duration_seconds: HistogramVec,
 }
 
 impl Metrics {
@@ -102,6 +785,15 @@ impl Metrics {
         )
         .expect("payment counter vec should build");
 
+        let payment_challenges_total = IntCounterVec::new(
+            Opts::new(
+                "payment_challenges_total",
+                "x402 payment challenges issued (HTTP 402)",
+            ),
+            &["service"],
+        )
+        .expect("payment challenge counter vec should build");
+
         let creator_events_total = IntCounterVec::new(
             Opts::new("creator_events_total", "Creator skill metric events"),
             &["skill", "platform", "event_type"],
@@ -114,37 +806,152 @@ impl Metrics {
         )
         .expect("sponsor counter should build");
 
+        let settle_queue_depth = IntGauge::new(
+            "x402_settle_queue_depth",
+            "Verified payments waiting for background settlement",
+        )
+        .expect("settle queue gauge should build");
+
+        let pending_settlements = IntGauge::new(
+            "x402_pending_settlements",
+            "Payments still pending settlement as of the last reconciliation pass",
+        )
+        .expect("pending settlements gauge should build");
+
+        let settlement_reconciliations_total = IntCounterVec::new(
+            Opts::new(
+                "x402_settlement_reconciliations_total",
+                "Stuck pending payments retried by the reconciler, by outcome",
+            ),
+            &["outcome"],
+        )
+        .expect("settlement reconciliation counter vec should build");
+
+        let db_pool_timeouts_total = IntCounter::new(
+            "db_pool_timeouts_total",
+            "Requests rejected because no database connection was available in time",
+        )
+        .expect("db pool timeout counter should build");
+
+        let sponsored_call_logs_dropped_total = IntCounter::new(
+            "sponsored_call_logs_dropped_total",
+            "Sponsored API call log rows given up on after every insert attempt failed",
+        )
+        .expect("sponsored call log drop counter should build");
+
+        let budget_utilization = GaugeVec::new(
+            Opts::new(
+                "budget_utilization",
+                "Share of a sponsor budget spent, as of its last debit",
+            ),
+            &["kind", "id"],
+        )
+        .expect("budget utilization gauge vec should build");
+
+        let budget_webhook_attempts_total = IntCounterVec::new(
+            Opts::new(
+                "budget_webhook_attempts_total",
+                "Budget-exhausted webhook delivery attempts",
+            ),
+            &["outcome"],
+        )
+        .expect("budget webhook counter vec should build");
+
+        let facilitator_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "facilitator_request_duration_seconds",
+                "Time spent on x402 facilitator verify and settle calls, including failures",
+            ),
+            &["operation"],
+        )
+        .expect("facilitator duration histogram vec should build");
+
         registry
             .register(Box::new(http_requests_total.clone()))
             .expect("register http counter vec");
         registry
             .register(Box::new(payment_events_total.clone()))
             .expect("register payment counter vec");
+        registry
+            .register(Box::new(payment_challenges_total.clone()))
+            .expect("register payment challenge counter vec");
         registry
             .register(Box::new(creator_events_total.clone()))
             .expect("register creator counter vec");
         registry
             .register(Box::new(sponsor_spend_cents_total.clone()))
             .expect("register sponsor spend counter");
+        registry
+            .register(Box::new(settle_queue_depth.clone()))
+            .expect("register settle queue gauge");
+        registry
+            .register(Box::new(pending_settlements.clone()))
+            .expect("register pending settlements gauge");
+        registry
+            .register(Box::new(settlement_reconciliations_total.clone()))
+            .expect("register settlement reconciliation counter vec");
+        registry
+            .register(Box::new(db_pool_timeouts_total.clone()))
+            .expect("register db pool timeout counter");
+        registry
+            .register(Box::new(budget_utilization.clone()))
+            .expect("register budget utilization gauge vec");
+        registry
+            .register(Box::new(budget_webhook_attempts_total.clone()))
+            .expect("register budget webhook counter vec");
+        registry
+            .register(Box::new(sponsored_call_logs_dropped_total.clone()))
+            .expect("register sponsored call log drop counter");
+        registry
+            .register(Box::new(facilitator_request_duration_seconds.clone()))
+            .expect("register facilitator duration histogram vec");
 
         Self {
             registry,
             http_requests_total,
             payment_events_total,
+            payment_challenges_total,
             creator_events_total,
             sponsor_spend_cents_total,
+            settle_queue_depth,
+            pending_settlements,
+            settlement_reconciliations_total,
+            db_pool_timeouts_total,
+            budget_utilization,
+            budget_webhook_attempts_total,
+            sponsored_call_logs_dropped_total,
+            facilitator_request_duration_seconds,
         }
     }
+
+    /// `kind` is `campaign` or `sponsored_api`.
+    pub fn record_budget_utilization(&self, kind: &str, id: Uuid, remaining: u64, total: u64) {
+        self.budget_utilization
+            .with_label_values(&[kind, &id.to_string()])
+            .set(budget_utilization(remaining, total));
+    }
+
+    /// `operation` is `verify` or `settle`; observed whether or not the call succeeded.
+    pub fn observe_facilitator_request(&self, operation: &str, started: Instant) {
+        self.facilitator_request_duration_seconds
+            .with_label_values(&[operation])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Share of `total` already spent, in `0.0..=1.0`; a zero total counts as unspent.
+pub fn budget_utilization(remaining_cents: u64, total_cents: u64) -> f64 {
+    if total_cents == 0 {
+        return 0.0;
+    }
+    (1.0 - remaining_cents as f64 / total_cents as f64).clamp(0.0, 1.0)
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let http = Client::builder()
-            .timeout(Duration::from_secs(20))
-            .build()
-            .expect("http client should build");
-
         let config = AppConfig::from_env();
+        let http = config.http_client();
+        let facilitator_http = config.facilitator_http_client();
         let db = std::env::var("DATABASE_URL").ok().and_then(|url| {
             PgPoolOptions::new()
                 .max_connections(10)
@@ -156,17 +963,29 @@ impl AppState {
             metrics: Metrics::new(),
             db,
             http,
+            facilitator_http,
             config,
+            facilitator_health: None,
+            settle_queue: None,
         }
     }
 
+    pub fn cached_facilitator_health(&self) -> Option<DependencyStatus> {
+        self.facilitator_health
+            .as_ref()
+            .filter(|(checked, _)| {
+                checked.elapsed() < Duration::from_secs(FACILITATOR_HEALTH_CACHE_SECS)
+            })
+            .map(|(_, status)| status.clone())
+    }
+
     pub fn service_price(&self, service: &str) -> u64 {
         match service {
             "scraping" => 5,
             "design" => 8,
             "storage" => 3,
             "data-tooling" => 4,
-            _ => DEFAULT_PRICE_CENTS,
+            _ => self.config.default_price_cents,
         }
     }
 }
@@ -193,6 +1012,33 @@ pub struct CreateUserRequest {
     pub attributes: HashMap<String, String>,
 }
 
+/// One entry of the `POST /users:batch` response, in request order.
+#[derive(Debug, Serialize)]
+pub struct ProfileBatchResult {
+    pub index: usize,
+    /// What `POST /profiles` would have returned for this row alone.
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+    /// The `error` object `POST /profiles` would have returned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+/// `POST /users/lookup`: everything but `email` is only used when the profile is created.
+#[derive(Debug, Deserialize)]
+pub struct LookupUserRequest {
+    pub email: String,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub tools_used: Vec<String>,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Campaign {
     pub id: Uuid,
@@ -206,11 +1052,48 @@ pub struct Campaign {
     pub budget_remaining_cents: u64,
     #[serde(default)]
     pub query_urls: Vec<String>,
+    /// How long a task completion keeps qualifying for sponsorship; `None` means forever.
+    #[serde(default)]
+    pub task_valid_for_secs: Option<u64>,
     pub active: bool,
+    /// Who created the campaign; supplied by the caller until API-key auth lands.
+    #[serde(default)]
+    pub created_by: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Notified once each time a debit leaves too little budget for another call.
+    #[serde(default)]
+    pub budget_exhausted_webhook_url: Option<String>,
+    /// Last sponsored run debited from this campaign; `None` if it never paid for one.
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Planning figures in calls, using `subsidy_per_call_cents` as the per-call cost even
+    /// though real service prices vary.
+    #[serde(default)]
+    pub estimated_calls_remaining: u64,
+    #[serde(default)]
+    pub estimated_total_calls: u64,
+    /// Share of the budget spent: `1 - remaining / total`.
+    #[serde(default)]
+    pub utilization: f64,
+    #[serde(default)]
+    pub incomplete_task_behavior: IncompleteTaskBehavior,
 }
 
-#[derive(Debug, Deserialize)]
+impl Campaign {
+    pub fn estimate_calls(budget_cents: u64, subsidy_per_call_cents: u64) -> u64 {
+        budget_cents
+            .checked_div(subsidy_per_call_cents)
+            .unwrap_or_default()
+    }
+
+    /// What the sponsor pays for a call at `price`: the whole price, when the subsidy covers it.
+    /// Campaigns never part-fund a call, so `None` means the user has to pay for it in full.
+    pub fn sponsor_charge(&self, price: u64) -> Option<u64> {
+        (self.subsidy_per_call_cents >= price).then_some(price)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateCampaignRequest {
     pub name: String,
     pub sponsor: String,
@@ -223,6 +1106,30 @@ pub struct CreateCampaignRequest {
     pub budget_cents: u64,
     #[serde(default)]
     pub query_urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_valid_for_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_exhausted_webhook_url: Option<String>,
+    /// Defaults to `INCOMPLETE_TASK_BEHAVIOR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete_task_behavior: Option<IncompleteTaskBehavior>,
+}
+
+/// Column list matching `CampaignRow`, shared by every campaign select and `returning` clause.
+pub const CAMPAIGN_COLUMNS: &str = "id, name, sponsor, target_roles, target_tools, required_task, \
+     subsidy_per_call_cents, budget_total_cents, budget_remaining_cents, query_urls, \
+     task_valid_for_secs, active, created_by, created_at, last_used_at, \
+     budget_exhausted_webhook_url, incomplete_task_behavior";
+
+/// A campaign or sponsored API row plus its trigger-maintained `updated_at`, which single
+/// resource GETs send as `Last-Modified`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UpdatedRow<T> {
+    #[sqlx(flatten)]
+    pub row: T,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -237,14 +1144,26 @@ pub struct CampaignRow {
     pub budget_total_cents: i64,
     pub budget_remaining_cents: i64,
     pub query_urls: Vec<String>,
+    pub task_valid_for_secs: Option<i64>,
     pub active: bool,
+    pub created_by: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub budget_exhausted_webhook_url: Option<String>,
+    pub incomplete_task_behavior: String,
 }
 
 impl TryFrom<CampaignRow> for Campaign {
     type Error = String;
 
     fn try_from(value: CampaignRow) -> Result<Self, Self::Error> {
+        let subsidy_per_call_cents = u64::try_from(value.subsidy_per_call_cents)
+            .map_err(|_| "subsidy_per_call_cents must be non-negative".to_string())?;
+        let budget_total_cents = u64::try_from(value.budget_total_cents)
+            .map_err(|_| "budget_total_cents must be non-negative".to_string())?;
+        let budget_remaining_cents = u64::try_from(value.budget_remaining_cents)
+            .map_err(|_| "budget_remaining_cents must be non-negative".to_string())?;
+
         Ok(Self {
             id: value.id,
             name: value.name,
@@ -252,15 +1171,38 @@ impl TryFrom<CampaignRow> for Campaign {
             target_roles: value.target_roles,
             target_tools: value.target_tools,
             required_task: value.required_task,
-            subsidy_per_call_cents: u64::try_from(value.subsidy_per_call_cents)
-                .map_err(|_| "subsidy_per_call_cents must be non-negative".to_string())?,
-            budget_total_cents: u64::try_from(value.budget_total_cents)
-                .map_err(|_| "budget_total_cents must be non-negative".to_string())?,
-            budget_remaining_cents: u64::try_from(value.budget_remaining_cents)
-                .map_err(|_| "budget_remaining_cents must be non-negative".to_string())?,
+            subsidy_per_call_cents,
+            budget_total_cents,
+            budget_remaining_cents,
             query_urls: value.query_urls,
+            task_valid_for_secs: value
+                .task_valid_for_secs
+                .map(u64::try_from)
+                .transpose()
+                .map_err(|_| "task_valid_for_secs must be non-negative".to_string())?,
             active: value.active,
+            created_by: value.created_by,
             created_at: value.created_at,
+            last_used_at: value.last_used_at,
+            budget_exhausted_webhook_url: value.budget_exhausted_webhook_url,
+            estimated_calls_remaining: Campaign::estimate_calls(
+                budget_remaining_cents,
+                subsidy_per_call_cents,
+            ),
+            estimated_total_calls: Campaign::estimate_calls(
+                budget_total_cents,
+                subsidy_per_call_cents,
+            ),
+            utilization: budget_utilization(budget_remaining_cents, budget_total_cents),
+            incomplete_task_behavior: IncompleteTaskBehavior::parse(
+                &value.incomplete_task_behavior,
+            )
+            .ok_or_else(|| {
+                format!(
+                    "unknown incomplete_task_behavior '{}'",
+                    value.incomplete_task_behavior
+                )
+            })?,
         })
     }
 }
@@ -270,6 +1212,8 @@ pub struct CreateCampaignResponse {
     pub campaign: Campaign,
     pub campaign_url: String,
     pub dashboard_url: String,
+    /// Non-blocking notes about the campaign configuration.
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -283,7 +1227,7 @@ pub struct CampaignDiscoveryItem {
     pub sponsored_api_discovery_url: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct TaskCompletion {
     pub id: Uuid,
     pub campaign_id: Uuid,
@@ -293,7 +1237,7 @@ pub struct TaskCompletion {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct TaskCompletionRequest {
     pub campaign_id: Uuid,
     pub user_id: Uuid,
@@ -301,29 +1245,104 @@ pub struct TaskCompletionRequest {
     pub details: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskCompletionsQuery {
+    pub task_name: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceRunRequest {
-    pub user_id: Uuid,
+    /// Required by `/proxy`; pay-per-call `/tool` runs may stay anonymous.
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
+    /// Free-form label recorded on the payment row for attribution.
+    #[serde(default)]
+    pub caller: Option<String>,
     pub input: String,
+    #[serde(default, skip_serializing)]
+    pub payment: Option<String>,
+    /// `/proxy` only: records this completion before sponsorship is evaluated, in the same
+    /// transaction. Ignored when paying with `PAYMENT-SIGNATURE`.
+    #[serde(default, skip_serializing)]
+    pub complete_task: Option<TaskCompletionRequest>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ServiceRunResponse {
     pub service: String,
     pub output: String,
-    pub payment_mode: String,
+    pub payment_mode: PaymentMode,
     pub sponsored_by: Option<String>,
+    /// The campaign that funded a sponsored call; `null` when the caller paid.
+    pub campaign_id: Option<Uuid>,
     pub tx_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PaymentRequired {
     pub service: String,
+    /// Matches the `x402-version` response header.
+    pub x402_version: String,
     pub amount_cents: u64,
     pub accepted_header: String,
     pub payment_required: String,
+    pub requirements: Vec<X402PaymentRequirement>,
     pub message: String,
     pub next_step: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+/// What `POST /sponsored-apis` charges, so a client can pay on its first attempt.
+#[derive(Debug, Clone, Serialize)]
+pub struct SponsoredApiCreatePrice {
+    pub service: String,
+    pub x402_version: String,
+    pub price_cents: u64,
+    /// The requirements a `402` from `POST /sponsored-apis` would carry; empty when it is free.
+    pub requirements: Vec<X402PaymentRequirement>,
+}
+
+/// Which targeting criteria a user met for one campaign. An empty target list always matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchResult {
+    pub matched: bool,
+    pub role_matched: bool,
+    pub tool_matched: bool,
+}
+
+/// A `pending` payment the reconciler retries.
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingPaymentRow {
+    pub settlement_job: sqlx::types::Json<SettlementJob>,
+    pub settle_attempts: i32,
+}
+
+/// Funded campaigns and how many of them miss a user's roles or tools.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TargetingMissesRow {
+    pub active_campaigns: i64,
+    pub unmatched_roles: i64,
+    pub unmatched_tools: i64,
+    pub matched_budget_exhausted: i64,
+}
+
+/// Why no campaign sponsored a proxy call, so agents can tell "do a task" from "just pay".
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SponsorEligibility {
+    pub active_campaigns: u64,
+    pub matched_incomplete_task: u64,
+    pub matched_budget_exhausted: u64,
+    /// Matched campaigns whose `subsidy_per_call_cents` is below the service price.
+    pub matched_subsidy_below_price: u64,
+    /// Active campaigns whose `target_roles` the user missed.
+    pub unmatched_roles: u64,
+    /// Active campaigns whose `target_tools` the user missed.
+    pub unmatched_tools: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -338,6 +1357,69 @@ pub enum PaymentSource {
 pub enum PaymentStatus {
     Settled,
     Failed,
+    /// Queued for async settlement; never reported by x402scan.
+    #[serde(skip_deserializing)]
+    Pending,
+}
+
+impl PaymentSource {
+    /// Value stored in `payments.source`.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Sponsor => "sponsor",
+        }
+    }
+
+    pub fn payment_mode(&self) -> PaymentMode {
+        match self {
+            Self::User => PaymentMode::UserDirect,
+            Self::Sponsor => PaymentMode::Sponsored,
+        }
+    }
+}
+
+/// Who paid for a call, as reported in `payment_mode` fields, the `x-payment-mode` header,
+/// and the `payment_events_total{mode}` label.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMode {
+    UserDirect,
+    Sponsored,
+}
+
+impl PaymentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UserDirect => "user_direct",
+            Self::Sponsored => "sponsored",
+        }
+    }
+
+    /// Value stored in `sponsored_api_calls.payment_mode`; identical to the wire form so SQL
+    /// filters and metric labels line up.
+    pub fn as_db_str(&self) -> &'static str {
+        self.as_str()
+    }
+
+    pub fn from_db_str(value: &str) -> Option<Self> {
+        match value {
+            "user_direct" => Some(Self::UserDirect),
+            "sponsored" => Some(Self::Sponsored),
+            _ => None,
+        }
+    }
+}
+
+impl PaymentStatus {
+    /// Value stored in `payments.status`, also used as the metric status label.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            Self::Settled => "settled",
+            Self::Failed => "failed",
+            Self::Pending => "pending",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -361,8 +1443,9 @@ pub struct X402PaymentRequirement {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct X402VerifyResponse {
+    #[serde(alias = "is_valid", alias = "valid")]
     pub is_valid: bool,
-    #[serde(default)]
+    #[serde(default, alias = "invalid_reason")]
     pub invalid_reason: Option<String>,
     #[serde(default)]
     pub payer: Option<String>,
@@ -372,14 +1455,21 @@ pub struct X402VerifyResponse {
 #[serde(rename_all = "camelCase")]
 pub struct X402SettleResponse {
     pub success: bool,
-    #[serde(default)]
+    #[serde(default, alias = "tx_hash", alias = "txHash")]
     pub transaction: Option<String>,
     #[serde(default)]
     pub payer: Option<String>,
-    #[serde(default)]
+    #[serde(default, alias = "error_reason")]
     pub error_reason: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct X402ScanBatchResponse {
+    pub inserted: usize,
+    /// Settlements whose `tx_hash` was already recorded.
+    pub skipped: usize,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct X402ScanSettlementRequest {
     pub tx_hash: String,
@@ -389,6 +1479,10 @@ pub struct X402ScanSettlementRequest {
     pub source: PaymentSource,
     pub status: PaymentStatus,
     pub campaign_id: Option<Uuid>,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub scheme: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -428,15 +1522,242 @@ pub struct SkillMetrics {
     pub last_seen_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SkillMetricsRow {
+    pub skill_name: String,
+    pub total_events: i64,
+    pub success_events: i64,
+    pub avg_duration_ms: Option<f64>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SponsorDashboard {
     pub campaign: Campaign,
     pub tasks_completed: usize,
     pub sponsored_calls: usize,
+    /// Distinct users behind `sponsored_calls`; payments without a user are not counted.
+    pub distinct_users: u64,
     pub spend_cents: u64,
     pub remaining_budget_cents: u64,
 }
 
+#[derive(Debug, Serialize)]
+pub struct AdminStats {
+    pub total_campaigns: u64,
+    pub active_campaigns: u64,
+    pub sponsored_spend_cents: u64,
+    pub user_direct_spend_cents: u64,
+    pub sponsored_api_calls: u64,
+    pub total_users: u64,
+    /// Settled spend per on-chain network and scheme; off-chain sponsor debits are excluded.
+    pub spend_by_network: Vec<NetworkSpend>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AdminStatsRow {
+    pub total_campaigns: i64,
+    pub active_campaigns: i64,
+    pub sponsored_spend_cents: i64,
+    pub user_direct_spend_cents: i64,
+    pub sponsored_api_calls: i64,
+    pub total_users: i64,
+}
+
+impl TryFrom<AdminStatsRow> for AdminStats {
+    type Error = String;
+
+    fn try_from(value: AdminStatsRow) -> Result<Self, Self::Error> {
+        let non_negative = |field: &str, value: i64| {
+            u64::try_from(value).map_err(|_| format!("{field} must be non-negative"))
+        };
+
+        Ok(Self {
+            total_campaigns: non_negative("total_campaigns", value.total_campaigns)?,
+            active_campaigns: non_negative("active_campaigns", value.active_campaigns)?,
+            sponsored_spend_cents: non_negative(
+                "sponsored_spend_cents",
+                value.sponsored_spend_cents,
+            )?,
+            user_direct_spend_cents: non_negative(
+                "user_direct_spend_cents",
+                value.user_direct_spend_cents,
+            )?,
+            sponsored_api_calls: non_negative("sponsored_api_calls", value.sponsored_api_calls)?,
+            total_users: non_negative("total_users", value.total_users)?,
+            spend_by_network: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkSpend {
+    pub network: String,
+    pub scheme: String,
+    pub source: String,
+    pub spend_cents: u64,
+    pub payment_count: u64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct NetworkSpendRow {
+    pub network: String,
+    pub scheme: String,
+    pub source: String,
+    pub spend_cents: i64,
+    pub payment_count: i64,
+}
+
+impl TryFrom<NetworkSpendRow> for NetworkSpend {
+    type Error = String;
+
+    fn try_from(value: NetworkSpendRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            network: value.network,
+            scheme: value.scheme,
+            source: value.source,
+            spend_cents: u64::try_from(value.spend_cents)
+                .map_err(|_| "spend_cents must be non-negative".to_string())?,
+            payment_count: u64::try_from(value.payment_count)
+                .map_err(|_| "payment_count must be non-negative".to_string())?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SinceQuery {
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Body POSTed to a `budget_exhausted_webhook_url`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetExhaustedEvent {
+    /// `campaign` or `sponsored_api`.
+    pub kind: &'static str,
+    pub id: Uuid,
+    pub sponsor: String,
+    pub budget_remaining_cents: u64,
+    pub exhausted_at: DateTime<Utc>,
+}
+
+/// Sort order for the campaign and sponsored API list endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder {
+    #[default]
+    Created,
+    /// Most recently used first; never-used entries sort last.
+    LastUsed,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListOrderQuery {
+    #[serde(default)]
+    pub order: ListOrder,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserSpendSummary {
+    pub user_id: Uuid,
+    pub user_direct_spend_cents: u64,
+    pub sponsored_spend_cents: u64,
+    pub call_count: u64,
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserSpendRow {
+    pub user_direct_spend_cents: i64,
+    pub sponsored_spend_cents: i64,
+    pub call_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EligibleUsersQuery {
+    /// Sample size; `0` returns only the count.
+    pub limit: Option<i64>,
+}
+
+/// Reach estimate for a campaign's targeting, counted over every stored profile.
+#[derive(Debug, Serialize)]
+pub struct EligibleUsers {
+    pub campaign_id: Uuid,
+    pub eligible_users: u64,
+    /// Newest matching profiles first.
+    pub sample: Vec<UserProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopSponsorsQuery {
+    pub limit: Option<i64>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopSponsor {
+    pub sponsor: String,
+    pub spend_cents: u64,
+    pub call_count: u64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TopSponsorRow {
+    pub sponsor: String,
+    pub spend_cents: i64,
+    pub call_count: i64,
+}
+
+impl TryFrom<TopSponsorRow> for TopSponsor {
+    type Error = String;
+
+    fn try_from(value: TopSponsorRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sponsor: value.sponsor,
+            spend_cents: u64::try_from(value.spend_cents)
+                .map_err(|_| "spend_cents must be non-negative".to_string())?,
+            call_count: u64::try_from(value.call_count)
+                .map_err(|_| "call_count must be non-negative".to_string())?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub message: String,
+    pub database: DependencyStatus,
+    pub facilitator: DependencyStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyStatus {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl DependencyStatus {
+    pub fn up(detail: Option<String>) -> Self {
+        Self::with_status("up", detail)
+    }
+
+    pub fn down(detail: impl Into<String>) -> Self {
+        Self::with_status("down", Some(detail.into()))
+    }
+
+    pub fn not_configured(detail: impl Into<String>) -> Self {
+        Self::with_status("not_configured", Some(detail.into()))
+    }
+
+    fn with_status(status: &str, detail: Option<String>) -> Self {
+        Self {
+            status: status.to_string(),
+            detail,
+            checked_at: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct MessageResponse {
     pub message: String,
@@ -452,14 +1773,61 @@ pub struct SponsoredApi {
     pub upstream_method: String,
     #[serde(default)]
     pub upstream_headers: HashMap<String, String>,
+    #[serde(default)]
+    pub forward_headers: Vec<String>,
     pub price_cents: u64,
     pub budget_total_cents: u64,
     pub budget_remaining_cents: u64,
     pub active: bool,
+    /// When false, a failed upstream call (transport error or a status outside
+    /// `success_status_codes`) refunds the sponsor.
+    #[serde(default)]
+    pub charge_on_upstream_error: bool,
+    /// Upstream statuses that count as billable success; empty means any 2xx.
+    #[serde(default)]
+    pub success_status_codes: Vec<u16>,
+    /// Response media types relayed to callers (`type/*` wildcards allowed); empty allows any.
+    #[serde(default)]
+    pub allowed_response_content_types: Vec<String>,
     pub service_key: String,
+    /// Who registered the API; supplied by the caller until API-key auth lands.
+    #[serde(default)]
+    pub created_by: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Overrides `X402_SCHEME` for user-paid runs of this API.
+    #[serde(default)]
+    pub x402_scheme: Option<X402Scheme>,
+    /// JSON Schema that run `input` must match before the upstream is called or anyone pays.
+    #[serde(default)]
+    pub input_schema: Option<Value>,
+    /// JSON Schema of the upstream response, advertised in x402 challenges.
+    #[serde(default)]
+    pub output_schema: Option<Value>,
+    /// Follows upstream redirects to public addresses, up to `SPONSORED_API_MAX_REDIRECTS`.
+    #[serde(default)]
+    pub follow_redirects: bool,
+    /// Notified once each time a debit leaves too little budget for another call.
+    #[serde(default)]
+    pub budget_exhausted_webhook_url: Option<String>,
+    /// Last successful run through this API; `None` if it has never been called.
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Share of the budget spent: `1 - remaining / total`.
+    #[serde(default)]
+    pub utilization: f64,
+    /// Distinct non-null callers across all runs; only filled in by `GET /sponsored-apis/{id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distinct_callers: Option<u64>,
 }
 
+/// Column list matching `SponsoredApiRow`, shared by every sponsored API select and `returning`
+/// clause.
+pub const SPONSORED_API_COLUMNS: &str = "id, name, sponsor, description, upstream_url, \
+     upstream_method, upstream_headers, forward_headers, price_cents, budget_total_cents, \
+     budget_remaining_cents, active, charge_on_upstream_error, success_status_codes, \
+     allowed_response_content_types, service_key, created_by, created_at, last_used_at, \
+     budget_exhausted_webhook_url, x402_scheme, input_schema, output_schema, follow_redirects";
+
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct SponsoredApiRow {
     pub id: Uuid,
@@ -469,18 +1837,34 @@ pub struct SponsoredApiRow {
     pub upstream_url: String,
     pub upstream_method: String,
     pub upstream_headers: sqlx::types::Json<HashMap<String, String>>,
+    pub forward_headers: Vec<String>,
     pub price_cents: i64,
     pub budget_total_cents: i64,
     pub budget_remaining_cents: i64,
     pub active: bool,
+    pub charge_on_upstream_error: bool,
+    pub success_status_codes: Vec<i32>,
+    pub allowed_response_content_types: Vec<String>,
     pub service_key: String,
+    pub created_by: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub budget_exhausted_webhook_url: Option<String>,
+    pub x402_scheme: Option<String>,
+    pub input_schema: Option<sqlx::types::Json<Value>>,
+    pub output_schema: Option<sqlx::types::Json<Value>>,
+    pub follow_redirects: bool,
 }
 
 impl TryFrom<SponsoredApiRow> for SponsoredApi {
     type Error = String;
 
     fn try_from(value: SponsoredApiRow) -> Result<Self, Self::Error> {
+        let budget_total_cents = u64::try_from(value.budget_total_cents)
+            .map_err(|_| "budget_total_cents must be non-negative".to_string())?;
+        let budget_remaining_cents = u64::try_from(value.budget_remaining_cents)
+            .map_err(|_| "budget_remaining_cents must be non-negative".to_string())?;
+
         Ok(Self {
             id: value.id,
             name: value.name,
@@ -489,19 +1873,77 @@ impl TryFrom<SponsoredApiRow> for SponsoredApi {
             upstream_url: value.upstream_url,
             upstream_method: value.upstream_method,
             upstream_headers: value.upstream_headers.0,
+            forward_headers: value.forward_headers,
             price_cents: u64::try_from(value.price_cents)
                 .map_err(|_| "price_cents must be non-negative".to_string())?,
-            budget_total_cents: u64::try_from(value.budget_total_cents)
-                .map_err(|_| "budget_total_cents must be non-negative".to_string())?,
-            budget_remaining_cents: u64::try_from(value.budget_remaining_cents)
-                .map_err(|_| "budget_remaining_cents must be non-negative".to_string())?,
+            budget_total_cents,
+            budget_remaining_cents,
             active: value.active,
+            charge_on_upstream_error: value.charge_on_upstream_error,
+            success_status_codes: value
+                .success_status_codes
+                .into_iter()
+                .map(|code| {
+                    u16::try_from(code)
+                        .map_err(|_| "success_status_codes must be HTTP status codes".to_string())
+                })
+                .collect::<Result<_, _>>()?,
+            allowed_response_content_types: value.allowed_response_content_types,
             service_key: value.service_key,
+            created_by: value.created_by,
             created_at: value.created_at,
+            last_used_at: value.last_used_at,
+            budget_exhausted_webhook_url: value.budget_exhausted_webhook_url,
+            x402_scheme: value
+                .x402_scheme
+                .map(|scheme| {
+                    X402Scheme::parse(&scheme)
+                        .ok_or_else(|| format!("unsupported x402_scheme '{scheme}'"))
+                })
+                .transpose()?,
+            input_schema: value.input_schema.map(|schema| schema.0),
+            output_schema: value.output_schema.map(|schema| schema.0),
+            follow_redirects: value.follow_redirects,
+            utilization: budget_utilization(budget_remaining_cents, budget_total_cents),
+            distinct_callers: None,
         })
     }
 }
 
+impl SponsoredApi {
+    pub fn counts_as_success(&self, status: StatusCode) -> bool {
+        if self.success_status_codes.is_empty() {
+            status.is_success()
+        } else {
+            self.success_status_codes.contains(&status.as_u16())
+        }
+    }
+
+    /// Matches the media type of a `content-type` value, ignoring parameters like `charset`.
+    pub fn allows_content_type(&self, content_type: Option<&str>) -> bool {
+        if self.allowed_response_content_types.is_empty() {
+            return true;
+        }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.allowed_response_content_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(top_level) => essence
+                    .split_once('/')
+                    .is_some_and(|(kind, _)| kind == top_level),
+                None => *allowed == essence,
+            })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateSponsoredApiRequest {
     pub name: String,
@@ -513,8 +1955,40 @@ pub struct CreateSponsoredApiRequest {
     #[serde(default)]
     pub upstream_headers: HashMap<String, String>,
     #[serde(default)]
+    pub forward_headers: Vec<String>,
+    #[serde(default)]
     pub price_cents: Option<u64>,
     pub budget_cents: u64,
+    #[serde(default)]
+    pub charge_on_upstream_error: bool,
+    #[serde(default)]
+    pub success_status_codes: Vec<u16>,
+    #[serde(default)]
+    pub allowed_response_content_types: Vec<String>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub budget_exhausted_webhook_url: Option<String>,
+    #[serde(default)]
+    pub x402_scheme: Option<String>,
+    #[serde(default)]
+    pub input_schema: Option<Value>,
+    #[serde(default)]
+    pub output_schema: Option<Value>,
+    #[serde(default)]
+    pub follow_redirects: bool,
+    /// Skips `SPONSORED_API_CREATE_PRICE_CENTS`; requires the management key.
+    #[serde(default)]
+    pub waive_create_fee: bool,
+}
+
+/// One rejected `upstream_headers` entry, listed in validation error `details.errors`.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamHeaderError {
+    pub header: String,
+    /// `name` or `value`.
+    pub part: &'static str,
+    pub message: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -523,15 +1997,29 @@ pub struct SponsoredApiRunRequest {
     pub caller: Option<String>,
     #[serde(default)]
     pub input: Value,
+    #[serde(default, skip_serializing)]
+    pub payment: Option<String>,
+    /// Upstream timeout for this call, from 1 to `SPONSORED_API_MAX_TIMEOUT_SECS`.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SponsoredApiRunQuery {
+    #[serde(default)]
+    pub stream: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SponsoredApiRunResponse {
     pub api_id: Uuid,
-    pub payment_mode: String,
+    pub payment_mode: PaymentMode,
     pub sponsored_by: Option<String>,
     pub tx_hash: Option<String>,
+    /// False when the sponsor was refunded because the upstream failed.
+    pub charged: bool,
     pub upstream_status: u16,
+    pub upstream_duration_ms: u64,
     pub upstream_body: String,
 }
 
@@ -539,16 +2027,356 @@ pub struct SponsoredApiRunResponse {
 pub struct SponsoredApiCall {
     pub id: Uuid,
     pub sponsored_api_id: Uuid,
-    pub payment_mode: String,
+    pub payment_mode: PaymentMode,
     pub amount_cents: u64,
     pub tx_hash: Option<String>,
     pub caller: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SponsoredApiCallRow {
+    pub id: Uuid,
+    pub sponsored_api_id: Uuid,
+    pub payment_mode: String,
+    pub amount_cents: i64,
+    pub tx_hash: Option<String>,
+    pub caller: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<SponsoredApiCallRow> for SponsoredApiCall {
+    type Error = String;
+
+    fn try_from(value: SponsoredApiCallRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            sponsored_api_id: value.sponsored_api_id,
+            payment_mode: PaymentMode::from_db_str(&value.payment_mode)
+                .ok_or_else(|| format!("unknown payment_mode '{}'", value.payment_mode))?,
+            amount_cents: u64::try_from(value.amount_cents)
+                .map_err(|_| "amount_cents must be non-negative".to_string())?,
+            tx_hash: value.tx_hash,
+            caller: value.caller,
+            created_at: value.created_at,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SponsoredApiCallsQuery {
+    pub payment_mode: Option<PaymentMode>,
+    pub caller: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// One management mutation. `before` is empty for creations and `after` for deletions.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor: Option<String>,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How a `/proxy/{service}/run` call was paid for, or why no campaign sponsored it. Logged to
+/// `proxy_decisions` as a request-to-sponsorship funnel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyDecision {
+    SponsoredPaid,
+    UserPaid,
+    TaskIncomplete,
+    BudgetExhausted,
+    SubsidyBelowPrice,
+    NoMatch,
+}
+
+impl ProxyDecision {
+    pub const ALL: [Self; 6] = [
+        Self::SponsoredPaid,
+        Self::UserPaid,
+        Self::TaskIncomplete,
+        Self::BudgetExhausted,
+        Self::SubsidyBelowPrice,
+        Self::NoMatch,
+    ];
+
+    /// Value stored in `proxy_decisions.decision`.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            Self::SponsoredPaid => "sponsored_paid",
+            Self::UserPaid => "user_paid",
+            Self::TaskIncomplete => "task_incomplete",
+            Self::BudgetExhausted => "budget_exhausted",
+            Self::SubsidyBelowPrice => "subsidy_below_price",
+            Self::NoMatch => "no_match",
+        }
+    }
+
+    /// The most specific reason nothing was sponsored, given the campaigns the user matched.
+    pub fn unsponsored(eligibility: &SponsorEligibility) -> Self {
+        if eligibility.matched_incomplete_task > 0 {
+            Self::TaskIncomplete
+        } else if eligibility.matched_budget_exhausted > 0 {
+            Self::BudgetExhausted
+        } else if eligibility.matched_subsidy_below_price > 0 {
+            Self::SubsidyBelowPrice
+        } else {
+            Self::NoMatch
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyDecisionEvent {
+    pub decision: ProxyDecision,
+    pub user_id: Uuid,
+    pub service: String,
+    /// The campaign that paid, or the one whose task is still incomplete.
+    pub campaign_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProxyDecisionsQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// `GET /admin/proxy-decisions`: counts per decision, with every decision listed.
+#[derive(Debug, Serialize)]
+pub struct ProxyDecisionCounts {
+    pub total: u64,
+    pub decisions: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProxyDecisionCountRow {
+    pub decision: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// Parses `SERVICE_OUTPUT_SCHEMAS`, a JSON object mapping a service name to the JSON Schema of
+/// its response, into each service's x402 `outputSchema`.
+fn parse_service_output_schemas(value: &str) -> HashMap<String, Value> {
+    let schemas: HashMap<String, Value> = serde_json::from_str(value)
+        .unwrap_or_else(|err| panic!("SERVICE_OUTPUT_SCHEMAS must be a JSON object: {err}"));
+    schemas
+        .into_iter()
+        .map(|(service, schema)| {
+            if let Err(err) = check_schema(&schema) {
+                panic!("SERVICE_OUTPUT_SCHEMAS entry '{service}' is invalid: {err}");
+            }
+            let output_schema = x402_output_schema(None, Some(&schema))
+                .expect("an output schema is always advertised");
+            (service, output_schema)
+        })
+        .collect()
+}
+
+/// The x402 `outputSchema` of a requirement: the request contract under `input` and the
+/// response contract under `output`. `None` when neither is known.
+pub fn x402_output_schema(input: Option<&Value>, output: Option<&Value>) -> Option<Value> {
+    if input.is_none() && output.is_none() {
+        return None;
+    }
+    let mut schema = serde_json::Map::new();
+    if let Some(input) = input {
+        schema.insert(
+            "input".to_string(),
+            serde_json::json!({ "type": "http", "method": "POST", "body": input }),
+        );
+    }
+    if let Some(output) = output {
+        schema.insert("output".to_string(), output.clone());
+    }
+    Some(Value::Object(schema))
+}
+
+/// Prices and limits where `0` is meaningful (free, none allowed, or disabled). A value that
+/// does not parse falls back to `default` with a warning.
 fn read_env_u64(key: &str, default: u64) -> u64 {
-    std::env::var(key)
+    let Ok(value) = std::env::var(key) else {
+        return default;
+    };
+    value.trim().parse::<u64>().unwrap_or_else(|_| {
+        warn!("{key}='{value}' is not a non-negative integer; using the default {default}");
+        default
+    })
+}
+
+/// Timeouts, intervals, and capacities, where `0` would break the feature rather than mean
+/// "no limit".
+fn read_env_nonzero_u64(key: &str, default: u64) -> u64 {
+    nonzero_or_default(key, read_env_u64(key, default), default)
+}
+
+pub fn nonzero_or_default(key: &str, value: u64, default: u64) -> u64 {
+    if value == 0 {
+        warn!("{key} must be greater than zero; using the default {default}");
+        return default;
+    }
+    value
+}
+
+/// `1`, `true`, `yes`, or `on` (any case); anything else, or unset, is `false`.
+fn read_env_bool(key: &str) -> bool {
+    std::env::var(key).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    })
+}
+
+/// `X402_PAYMENT_RESPONSE_HEADER` as a comma-separated list of header names, e.g.
+/// `x-payment-response,payment-response` to serve older and newer clients at once; unset or
+/// blank uses `PAYMENT_RESPONSE_HEADER`.
+pub fn read_payment_response_headers() -> Vec<HeaderName> {
+    let configured = std::env::var("X402_PAYMENT_RESPONSE_HEADER").unwrap_or_default();
+    let mut names: Vec<HeaderName> = Vec::new();
+    for name in configured
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let name = HeaderName::from_bytes(name.as_bytes()).unwrap_or_else(|_| {
+            panic!("X402_PAYMENT_RESPONSE_HEADER '{name}' is not a valid header name")
+        });
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        names.push(HeaderName::from_static(PAYMENT_RESPONSE_HEADER));
+    }
+    names
+}
+
+/// Host name labels that mark a non-production deployment, e.g. `staging.example.com` or
+/// `api-dev.example.com`.
+const NON_PRODUCTION_HOST_LABELS: [&str; 7] = [
+    "localhost",
+    "staging",
+    "stage",
+    "dev",
+    "test",
+    "sandbox",
+    "preview",
+];
+
+/// The longest a paid sponsored run can spend on outbound calls: a facilitator verify and
+/// settle, each up to `FACILITATOR_TIMEOUT_SECS`, then the upstream call.
+pub const fn slowest_paid_run_secs(
+    facilitator_timeout_secs: u64,
+    sponsored_api_max_timeout_secs: u64,
+) -> u64 {
+    facilitator_timeout_secs
+        .saturating_mul(2)
+        .saturating_add(sponsored_api_max_timeout_secs)
+}
+
+/// True unless the URL points at a non-public address, a `.local`/`.test`/
+/// `.localhost`/`.internal` name, or a host with a staging/dev-style label. An unparseable
+/// URL counts as production.
+pub fn looks_like_production_url(url: &str) -> bool {
+    let Some(host) = reqwest::Url::parse(url)
         .ok()
-        .and_then(|value| value.parse::<u64>().ok())
-        .unwrap_or(default)
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+    else {
+        return true;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return is_public_ip(ip);
+    }
+    let private_suffix = [".local", ".test", ".localhost", ".internal"]
+        .iter()
+        .any(|suffix| host.ends_with(suffix));
+    let non_production_label = host
+        .split(['.', '-'])
+        .any(|label| NON_PRODUCTION_HOST_LABELS.contains(&label));
+    !(private_suffix || non_production_label)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReconcileRequest {
+    /// Only local rows created at or after this instant are reported as `extra`; set it to the
+    /// start of the exported x402scan window. Defaults to the oldest matched payment.
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    pub settlements: Vec<ReconcileEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileEntry {
+    pub tx_hash: String,
+    pub amount_cents: u64,
+    pub status: PaymentStatus,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReconcileMismatch {
+    pub tx_hash: String,
+    pub reported_amount_cents: u64,
+    pub reported_status: PaymentStatus,
+    pub recorded_amount_cents: u64,
+    pub recorded_status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordedPayment {
+    pub tx_hash: String,
+    pub amount_cents: u64,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RecordedPaymentRow {
+    pub tx_hash: String,
+    pub amount_cents: i64,
+    pub status: String,
+}
+
+impl TryFrom<RecordedPaymentRow> for RecordedPayment {
+    type Error = String;
+
+    fn try_from(value: RecordedPaymentRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tx_hash: value.tx_hash,
+            amount_cents: u64::try_from(value.amount_cents)
+                .map_err(|_| "amount_cents must be non-negative".to_string())?,
+            status: value.status,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReconcileReport {
+    pub checked: usize,
+    pub matched: usize,
+    /// Reported by x402scan but never recorded locally.
+    pub missing: Vec<ReconcileEntry>,
+    /// Recorded under the same `tx_hash` with a different amount or status.
+    pub mismatched: Vec<ReconcileMismatch>,
+    /// Settled user payments recorded locally that x402scan did not report.
+    pub extra: Vec<RecordedPayment>,
+    /// Set when more than `MAX_RECONCILE_EXTRA` extra payments exist; narrow `since` to see the rest.
+    pub extra_truncated: bool,
 }
@@ -1,30 +1,61 @@
 use axum::{
     Json,
-    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    body::Body,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use base64::{Engine as _, engine::general_purpose::STANDARD};
-use reqwest::{Client, Method};
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, Url};
+use serde::Serialize;
 use serde_json::Value;
-use std::{collections::HashMap, time::Duration};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{self, Sender};
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::error::{ApiError, ApiResult};
-use crate::onchain::{VerifiedX402Payment, verify_and_settle_x402_payment};
+use crate::onchain::{
+    VerifiedX402Payment, payment_attempt, select_payment_requirement, settle_x402_signature,
+    validate_payment_signature, verify_and_settle_x402_payment, verify_x402_signature,
+};
 use crate::types::{
-    AppConfig, Campaign, Metrics, PAYMENT_RESPONSE_HEADER, PAYMENT_SIGNATURE_HEADER,
-    PaymentRequired, SPONSORED_API_SERVICE_PREFIX, ServiceRunRequest, ServiceRunResponse,
-    SponsoredApi, UserProfile, X402_VERSION_HEADER, X402PaymentRequirement,
+    AppConfig, AuditEntry, BUDGET_WEBHOOK_MAX_ATTEMPTS, BUDGET_WEBHOOK_TIMEOUT_SECS,
+    BudgetExhaustedEvent, CAMPAIGN_COLUMNS, Campaign, CampaignRow, CreateUserRequest,
+    DEFAULT_X402_ASSET_DECIMALS, DEFAULT_X402_SCHEME, DependencyStatus, HOP_BY_HOP_HEADERS,
+    HTTP_CLIENT_TIMEOUT_SECS, IDEMPOTENCY_KEY_HEADER, MAX_CALLER_LEN, MAX_IDEMPOTENCY_KEY_LEN,
+    MAX_REQUEST_ID_LEN, MAX_SETTLE_ATTEMPTS, MatchResult, Metrics, PAYMENT_SIGNATURE_HEADER,
+    PaymentAttribution, PaymentMode, PaymentRequired, PaymentSource, PaymentStatus,
+    PendingPaymentRow, ProfileBatchResult, ProxyDecisionEvent, RECONCILE_BATCH_SIZE,
+    REQUEST_ID_HEADER, RESERVED_SERVICES, SKIPPED_ROWS_HEADER, SPONSORED_API_COLUMNS,
+    SPONSORED_API_SERVICE_PREFIX, SPONSORED_CALL_LOG_MAX_ATTEMPTS,
+    SPONSORED_IDEMPOTENCY_KEY_TTL_SECS, ServiceRunRequest, ServiceRunResponse, SettlementJob,
+    SkillMetrics, SkillMetricsRow, SponsoredApi, SponsoredApiCall, TEST_MODE_PAYER,
+    TEST_MODE_TX_HASH_PREFIX, TOTAL_COUNT_HEADER, TargetingMissesRow, TaskCompletion,
+    TaskCompletionRequest, UpstreamHeaderError, UserProfile, X402_VERSION_HEADER,
+    X402PaymentRequirement, X402ScanSettlementRequest, X402SettleResponse,
 };
-use sqlx::PgPool;
-
-const USDC_BASE_UNITS_PER_CENT: u128 = 10_000;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 
 pub fn respond<T: IntoResponse>(
     metrics: &Metrics,
     endpoint: &str,
     result: ApiResult<T>,
 ) -> Response {
+    match &result {
+        Err(ApiError::PaymentRequired(challenge)) => metrics
+            .payment_challenges_total
+            .with_label_values(&[challenge.service.as_str()])
+            .inc(),
+        Err(ApiError::DatabaseUnavailable { .. }) => metrics.db_pool_timeouts_total.inc(),
+        _ => {}
+    }
+
     let response = match result {
         Ok(value) => value.into_response(),
         Err(err) => err.into_response(),
@@ -34,8 +65,8 @@ pub fn respond<T: IntoResponse>(
     response
 }
 
-pub fn user_matches_campaign(user: &UserProfile, campaign: &Campaign) -> bool {
-    let role_match = if campaign.target_roles.is_empty() {
+pub fn user_matches_campaign(user: &UserProfile, campaign: &Campaign) -> MatchResult {
+    let role_matched = if campaign.target_roles.is_empty() {
         true
     } else {
         user.roles
@@ -43,7 +74,7 @@ pub fn user_matches_campaign(user: &UserProfile, campaign: &Campaign) -> bool {
             .any(|role| campaign.target_roles.iter().any(|target| target == role))
     };
 
-    let tool_match = if campaign.target_tools.is_empty() {
+    let tool_matched = if campaign.target_tools.is_empty() {
         true
     } else {
         user.tools_used
@@ -51,14 +82,574 @@ pub fn user_matches_campaign(user: &UserProfile, campaign: &Campaign) -> bool {
             .any(|tool| campaign.target_tools.iter().any(|target| target == tool))
     };
 
-    role_match && tool_match
+    MatchResult {
+        matched: role_matched && tool_matched,
+        role_matched,
+        tool_matched,
+    }
+}
+
+/// Converts listed rows, logging and skipping any that fail so one corrupt row cannot hide
+/// the rest. Returns the converted items and how many rows were skipped.
+pub fn convert_list_rows<R, T>(
+    rows: Vec<R>,
+    entity: &str,
+    row_id: impl Fn(&R) -> Uuid,
+) -> (Vec<T>, usize)
+where
+    T: TryFrom<R, Error = String>,
+{
+    let mut skipped = 0;
+    let items = rows
+        .into_iter()
+        .filter_map(|row| {
+            let id = row_id(&row);
+            T::try_from(row)
+                .inspect_err(|err| {
+                    skipped += 1;
+                    warn!("skipping unreadable {entity} {id}: {err}");
+                })
+                .ok()
+        })
+        .collect();
+    (items, skipped)
 }
 
-pub async fn has_completed_task(
+/// A `200` list body, with `x-skipped-rows` when `convert_list_rows` left any rows out.
+pub fn list_response<T: Serialize>(items: T, skipped: usize) -> Response {
+    let mut response = (StatusCode::OK, Json(items)).into_response();
+    if skipped > 0 {
+        response.headers_mut().insert(
+            HeaderName::from_static(SKIPPED_ROWS_HEADER),
+            HeaderValue::from(skipped),
+        );
+    }
+    response
+}
+
+/// `list_response` plus `x-total-count`, the table's row count from a separate `count(*)`.
+pub fn counted_list_response<T: Serialize>(items: T, skipped: usize, total: i64) -> Response {
+    let mut response = list_response(items, skipped);
+    response.headers_mut().insert(
+        HeaderName::from_static(TOTAL_COUNT_HEADER),
+        HeaderValue::from(total),
+    );
+    response
+}
+
+/// `user_matches_campaign` as a filter on `users`, binding `target_roles` as `$1` and
+/// `target_tools` as `$2`. Keep the two in step.
+const USER_TARGETING_FILTER: &str = "(cardinality($1::text[]) = 0 or roles && $1::text[]) \
+     and (cardinality($2::text[]) = 0 or tools_used && $2::text[])";
+
+/// Counts the profiles `campaign` targets and returns up to `sample_limit` of them.
+pub async fn load_eligible_users(
     db: &PgPool,
+    campaign: &Campaign,
+    sample_limit: i64,
+) -> ApiResult<(u64, Vec<UserProfile>)> {
+    let count = sqlx::query_scalar::<_, i64>(&format!(
+        "select count(*) from users where {USER_TARGETING_FILTER}"
+    ))
+    .bind(&campaign.target_roles)
+    .bind(&campaign.target_tools)
+    .fetch_one(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    let sample = sqlx::query_as::<_, UserProfile>(&format!(
+        "select id, email, region, roles, tools_used, attributes, created_at \
+         from users where {USER_TARGETING_FILTER} \
+         order by created_at desc, id limit $3"
+    ))
+    .bind(&campaign.target_roles)
+    .bind(&campaign.target_tools)
+    .bind(sample_limit)
+    .fetch_all(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok((count as u64, sample))
+}
+
+/// `user_matches_campaign` as filters on `campaigns`, one per criterion, binding the user's
+/// `roles` as `$2` and `tools_used` as `$3`. Keep them in step too.
+const CAMPAIGN_ROLE_FILTER: &str = "(cardinality(target_roles) = 0 or target_roles && $2::text[])";
+const CAMPAIGN_TOOL_FILTER: &str = "(cardinality(target_tools) = 0 or target_tools && $3::text[])";
+
+/// A campaign that can fund a proxy call at `$1`.
+pub const CAMPAIGN_FUNDED_FILTER: &str = "(active = true and budget_remaining_cents >= $1)";
+/// A campaign that cannot fund a call at `$1` because its budget ran low: still active, or
+/// switched off by the proxy once the budget fell below a call. Paused campaigns that could
+/// still fund their own subsidy are left out.
+pub const CAMPAIGN_EXHAUSTED_FILTER: &str = "(budget_remaining_cents < $1 \
+     and (active = true or budget_remaining_cents < subsidy_per_call_cents))";
+
+/// Funded campaigns targeting `user` that a proxy run at `price` weighs, newest first.
+pub async fn load_targeted_campaigns<'e>(
+    executor: impl PgExecutor<'e>,
+    user: &UserProfile,
+    price: u64,
+) -> ApiResult<Vec<Campaign>> {
+    let rows = sqlx::query_as::<_, CampaignRow>(&campaign_select(&format!(
+        "where {CAMPAIGN_FUNDED_FILTER} \
+         and {CAMPAIGN_ROLE_FILTER} and {CAMPAIGN_TOOL_FILTER} \
+         order by created_at desc"
+    )))
+    .bind(price as i64)
+    .bind(&user.roles)
+    .bind(&user.tools_used)
+    .fetch_all(executor)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    // A corrupt campaign is left out rather than failing every proxy run.
+    let (campaigns, _) = convert_list_rows(rows, "campaign", |row: &CampaignRow| row.id);
+    Ok(campaigns)
+}
+
+/// Counts campaigns funded for a call at `price`, how many of them miss `user`'s roles or
+/// tools, and how many target `user` but ran out of budget.
+pub async fn load_targeting_misses<'e>(
+    executor: impl PgExecutor<'e>,
+    user: &UserProfile,
+    price: u64,
+) -> ApiResult<TargetingMissesRow> {
+    sqlx::query_as::<_, TargetingMissesRow>(&format!(
+        "select count(*) filter (where {CAMPAIGN_FUNDED_FILTER}) as active_campaigns, \
+             count(*) filter (where {CAMPAIGN_FUNDED_FILTER} and not {CAMPAIGN_ROLE_FILTER}) \
+                 as unmatched_roles, \
+             count(*) filter (where {CAMPAIGN_FUNDED_FILTER} and not {CAMPAIGN_TOOL_FILTER}) \
+                 as unmatched_tools, \
+             count(*) filter (where {CAMPAIGN_EXHAUSTED_FILTER} \
+                 and {CAMPAIGN_ROLE_FILTER} and {CAMPAIGN_TOOL_FILTER}) \
+                 as matched_budget_exhausted \
+         from campaigns \
+         where {CAMPAIGN_FUNDED_FILTER} or {CAMPAIGN_EXHAUSTED_FILTER}"
+    ))
+    .bind(price as i64)
+    .bind(&user.roles)
+    .bind(&user.tools_used)
+    .fetch_one(executor)
+    .await
+    .map_err(ApiError::from_sqlx)
+}
+
+/// Builds `select <CAMPAIGN_COLUMNS> from campaigns <clause>` for `CampaignRow` queries.
+pub fn campaign_select(clause: &str) -> String {
+    format!("select {CAMPAIGN_COLUMNS} from campaigns {clause}")
+}
+
+/// Builds `select <SPONSORED_API_COLUMNS> from sponsored_apis <clause>` for `SponsoredApiRow`
+/// queries.
+pub fn sponsored_api_select(clause: &str) -> String {
+    format!("select {SPONSORED_API_COLUMNS} from sponsored_apis {clause}")
+}
+
+/// Per-skill creator event totals, busiest first. `skill_name` breaks ties so the order is
+/// stable between calls.
+pub async fn load_skill_metrics<'e>(executor: impl PgExecutor<'e>) -> ApiResult<Vec<SkillMetrics>> {
+    let rows = sqlx::query_as::<_, SkillMetricsRow>(
+        r#"
+        select
+            skill_name,
+            count(*) as total_events,
+            count(*) filter (where success = true) as success_events,
+            avg(duration_ms) as avg_duration_ms,
+            max(created_at) as last_seen_at
+        from creator_events
+        group by skill_name
+        order by total_events desc, last_seen_at desc, skill_name
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| SkillMetrics {
+            skill_name: row.skill_name,
+            total_events: row.total_events as usize,
+            success_events: row.success_events as usize,
+            avg_duration_ms: row.avg_duration_ms,
+            last_seen_at: row.last_seen_at,
+        })
+        .collect())
+}
+
+/// Inserts one x402scan settlement, returning false when its `tx_hash` was already recorded.
+pub async fn insert_x402scan_settlement<'e>(
+    executor: impl PgExecutor<'e>,
+    default_network: &str,
+    settlement: &X402ScanSettlementRequest,
+) -> ApiResult<bool> {
+    // x402scan webhooks may omit these; settlements default to this deployment's network.
+    let network = settlement
+        .network
+        .clone()
+        .unwrap_or_else(|| default_network.to_string());
+    let scheme = settlement
+        .scheme
+        .clone()
+        .unwrap_or_else(|| DEFAULT_X402_SCHEME.to_string());
+
+    let result = sqlx::query(
+        r#"
+        insert into payments (
+            tx_hash, campaign_id, service, amount_cents, payer, source, status, created_at,
+            network, scheme
+        )
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        on conflict (tx_hash) do nothing
+        "#,
+    )
+    .bind(&settlement.tx_hash)
+    .bind(settlement.campaign_id)
+    .bind(&settlement.service)
+    .bind(cents_to_i64("amount_cents", settlement.amount_cents)?)
+    .bind(&settlement.payer)
+    .bind(settlement.source.as_db_str())
+    .bind(settlement.status.as_db_str())
+    .bind(Utc::now())
+    .bind(network)
+    .bind(scheme)
+    .execute(executor)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Writes an audit row on the caller's transaction so it commits or rolls back with the change.
+pub async fn record_audit(conn: &mut PgConnection, entry: &AuditEntry) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        insert into audit_log (
+            id, actor, action, entity_type, entity_id, before, after, created_at
+        )
+        values ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(entry.id)
+    .bind(&entry.actor)
+    .bind(&entry.action)
+    .bind(&entry.entity_type)
+    .bind(entry.entity_id)
+    .bind(&entry.before)
+    .bind(&entry.after)
+    .bind(entry.created_at)
+    .execute(conn)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok(())
+}
+
+/// Audit snapshot of a sponsored API with `upstream_headers` values masked, since they
+/// usually carry the sponsor's upstream credentials.
+pub fn sponsored_api_audit_snapshot(api: &SponsoredApi) -> Value {
+    let mut snapshot = serde_json::to_value(api).unwrap_or(Value::Null);
+    if let Some(headers) = snapshot
+        .get_mut("upstream_headers")
+        .and_then(Value::as_object_mut)
+    {
+        for value in headers.values_mut() {
+            *value = Value::String("[redacted]".to_string());
+        }
+    }
+    snapshot
+}
+
+/// Returns one call's price to a sponsored API's budget. An API the debit switched off for
+/// lacking another call's budget is switched back on; one paused with budget left stays off.
+pub async fn refund_sponsored_api_budget(db: &PgPool, api: &SponsoredApi) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        update sponsored_apis
+        set budget_remaining_cents = budget_remaining_cents + $1,
+            active = active or budget_remaining_cents < price_cents
+        where id = $2
+        "#,
+    )
+    .bind(api.price_cents as i64)
+    .bind(api.id)
+    .execute(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok(())
+}
+
+/// Validates an optional sponsor webhook URL. Only http(s) URLs whose host resolves solely
+/// to public addresses are accepted, so deliveries cannot be aimed at internal services.
+pub async fn validate_webhook_url(field: &str, url: Option<&str>) -> ApiResult<Option<String>> {
+    let Some(raw) = url.map(str::trim).filter(|url| !url.is_empty()) else {
+        return Ok(None);
+    };
+    let parsed = Url::parse(raw)
+        .map_err(|_| ApiError::validation_field(field, format!("{field} must be a valid URL")))?;
+    ensure_public_target(&parsed)
+        .await
+        .map_err(|reason| ApiError::validation_field(field, format!("{field} {reason}")))?;
+    Ok(Some(parsed.to_string()))
+}
+
+/// Checks that `url` is an http(s) URL whose host resolves only to public addresses, and
+/// returns those addresses so the caller can connect to exactly what was checked.
+async fn ensure_public_target(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("must use http or https".to_string());
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("must not embed credentials".to_string());
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| "must include a host".to_string())?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    // IPv6 literals keep their brackets in `host_str`.
+    let addresses: Vec<SocketAddr> = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(address) => vec![SocketAddr::new(address, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| format!("host '{host}' does not resolve"))?
+            .collect(),
+    };
+    if addresses.is_empty() {
+        return Err("host does not resolve".to_string());
+    }
+    if let Some(address) = addresses.iter().find(|address| !is_public_ip(address.ip())) {
+        return Err(format!(
+            "must not point at a private or local address ({})",
+            address.ip()
+        ));
+    }
+
+    Ok(addresses)
+}
+
+/// A one-off client for `url` that connects only to the public addresses just checked. The
+/// shared client would resolve the host again when connecting, so a DNS answer that changed
+/// in between (rebinding) could still reach an internal address.
+async fn pinned_public_client(url: &Url) -> Result<Client, String> {
+    let addresses = ensure_public_target(url).await?;
+    let host = url.host_str().unwrap_or_default();
+    Client::builder()
+        .timeout(Duration::from_secs(HTTP_CLIENT_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, &addresses)
+        .build()
+        .map_err(|err| format!("could not get a client for it: {err}"))
+}
+
+pub fn is_public_ip(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // 0.0.0.0/8 and the 100.64.0.0/10 carrier-grade NAT range.
+            let reserved = first == 0 || (first == 100 && (64..128).contains(&second));
+            !(reserved
+                || ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ip(IpAddr::V4(mapped)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Inserts a sponsored API call log row in the background. Budget and payment writes happen
+/// before this, so a failed insert is retried and then dropped, never surfaced to the caller.
+/// Retries reuse the row id, so an insert that committed but timed out is not duplicated.
+pub fn spawn_sponsored_call_log(db: PgPool, metrics: Metrics, call: SponsoredApiCall) {
+    tokio::spawn(async move {
+        for attempt in 1..=SPONSORED_CALL_LOG_MAX_ATTEMPTS {
+            let inserted = sqlx::query(
+                r#"
+                insert into sponsored_api_calls (
+                    id, sponsored_api_id, payment_mode, amount_cents, tx_hash, caller, created_at
+                ) values ($1, $2, $3, $4, $5, $6, $7)
+                on conflict (id) do nothing
+                "#,
+            )
+            .bind(call.id)
+            .bind(call.sponsored_api_id)
+            .bind(call.payment_mode.as_db_str())
+            .bind(call.amount_cents as i64)
+            .bind(&call.tx_hash)
+            .bind(&call.caller)
+            .bind(call.created_at)
+            .execute(&db)
+            .await;
+            let Err(err) = inserted else {
+                return;
+            };
+            warn!(
+                "failed to log sponsored api call {} (attempt {attempt}/{SPONSORED_CALL_LOG_MAX_ATTEMPTS}): {err}",
+                call.id
+            );
+            if attempt < SPONSORED_CALL_LOG_MAX_ATTEMPTS {
+                tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+            }
+        }
+        metrics.sponsored_call_logs_dropped_total.inc();
+    });
+}
+
+/// Counts a user_direct payment a handler accepted. One still queued for async settlement is
+/// `pending`; `finish_settlement` counts it again once it settles or fails.
+pub fn count_user_direct_payment(metrics: &Metrics, payment: &VerifiedX402Payment) {
+    let status = if payment.settlement_pending {
+        PaymentStatus::Pending
+    } else {
+        PaymentStatus::Settled
+    };
+    metrics
+        .payment_events_total
+        .with_label_values(&[PaymentMode::UserDirect.as_str(), status.as_db_str()])
+        .inc();
+}
+
+/// Records a settled user_direct payment in the background, so a request that then runs past
+/// `REQUEST_TIMEOUT_SECS` cannot drop the record of money already taken. Queued settlements
+/// are recorded by the worker instead.
+pub fn spawn_user_direct_payment_record(
+    db: PgPool,
+    service: String,
+    amount_cents: u64,
+    attribution: PaymentAttribution,
+    payment: VerifiedX402Payment,
+) {
+    if payment.settlement_pending {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(err) =
+            record_user_direct_payment(&db, &service, amount_cents, &attribution, &payment).await
+        {
+            warn!("failed to record user_direct payment: {err}");
+        }
+    });
+}
+
+/// Stamps `last_used_at` for a paid sponsored run in the background, for the same reason as
+/// [`spawn_user_direct_payment_record`].
+pub fn spawn_sponsored_api_use(db: PgPool, api_id: Uuid) {
+    tokio::spawn(async move {
+        if let Err(err) = sqlx::query("update sponsored_apis set last_used_at = $1 where id = $2")
+            .bind(Utc::now())
+            .bind(api_id)
+            .execute(&db)
+            .await
+        {
+            warn!("failed to record sponsored api {api_id} use: {err}");
+        }
+    });
+}
+
+/// Records a proxy decision in the background so analytics never add request latency.
+pub fn spawn_proxy_decision_log(db: PgPool, event: ProxyDecisionEvent) {
+    tokio::spawn(async move {
+        let inserted = sqlx::query(
+            r#"
+            insert into proxy_decisions (id, decision, user_id, service, campaign_id, created_at)
+            values ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(event.decision.as_db_str())
+        .bind(event.user_id)
+        .bind(&event.service)
+        .bind(event.campaign_id)
+        .bind(Utc::now())
+        .execute(&db)
+        .await;
+        if let Err(err) = inserted {
+            warn!(
+                "failed to record {} proxy decision for {}: {err}",
+                event.decision.as_db_str(),
+                event.service
+            );
+        }
+    });
+}
+
+/// Posts `event` to a sponsor's budget-exhausted webhook in the background, retrying with
+/// backoff, so the request that spent the last of the budget is not held up.
+pub fn spawn_budget_exhausted_webhook(metrics: Metrics, url: String, event: BudgetExhaustedEvent) {
+    tokio::spawn(async move {
+        let Ok(url) = Url::parse(&url) else {
+            warn!("invalid budget webhook url for {} {}", event.kind, event.id);
+            return;
+        };
+        // Checked again because the host may resolve differently than at registration.
+        let http = match pinned_public_client(&url).await {
+            Ok(http) => http,
+            Err(reason) => {
+                warn!(
+                    "skipping budget webhook for {} {}: url {reason}",
+                    event.kind, event.id
+                );
+                metrics
+                    .budget_webhook_attempts_total
+                    .with_label_values(&["blocked"])
+                    .inc();
+                return;
+            }
+        };
+
+        for attempt in 1..=BUDGET_WEBHOOK_MAX_ATTEMPTS {
+            let sent = http
+                .post(url.clone())
+                .timeout(Duration::from_secs(BUDGET_WEBHOOK_TIMEOUT_SECS))
+                .json(&event)
+                .send()
+                .await;
+            let failure = match sent {
+                Ok(response) if response.status().is_success() => {
+                    metrics
+                        .budget_webhook_attempts_total
+                        .with_label_values(&["delivered"])
+                        .inc();
+                    return;
+                }
+                Ok(response) => format!("status={}", response.status()),
+                Err(err) => err.to_string(),
+            };
+            metrics
+                .budget_webhook_attempts_total
+                .with_label_values(&["failed"])
+                .inc();
+            warn!(
+                "budget webhook for {} {} failed (attempt {attempt}/{BUDGET_WEBHOOK_MAX_ATTEMPTS}): {failure}",
+                event.kind, event.id
+            );
+            if attempt < BUDGET_WEBHOOK_MAX_ATTEMPTS {
+                tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+            }
+        }
+    });
+}
+
+pub async fn has_completed_task<'e>(
+    executor: impl PgExecutor<'e>,
     campaign_id: Uuid,
     user_id: Uuid,
     required_task: &str,
+    valid_for_secs: Option<u64>,
 ) -> ApiResult<bool> {
     let exists = sqlx::query_scalar::<_, bool>(
         r#"
@@ -67,31 +658,236 @@ pub async fn has_completed_task(
             where campaign_id = $1
               and user_id = $2
               and task_name = $3
+              and ($4::bigint is null or created_at >= now() - $4 * interval '1 second')
         )
         "#,
     )
     .bind(campaign_id)
     .bind(user_id)
     .bind(required_task)
-    .fetch_one(db)
+    .bind(valid_for_secs.map(|secs| secs as i64))
+    .fetch_one(executor)
     .await
-    .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    .map_err(ApiError::from_sqlx)?;
 
     Ok(exists)
 }
 
+/// Inserts a task completion, mapping a missing campaign or user to `404`.
+pub async fn record_task_completion<'e>(
+    executor: impl PgExecutor<'e>,
+    request: TaskCompletionRequest,
+) -> ApiResult<TaskCompletion> {
+    let completion = TaskCompletion {
+        id: Uuid::new_v4(),
+        campaign_id: request.campaign_id,
+        user_id: request.user_id,
+        task_name: request.task_name,
+        details: request.details,
+        created_at: Utc::now(),
+    };
+
+    sqlx::query(
+        r#"
+        insert into task_completions (id, campaign_id, user_id, task_name, details, created_at)
+        values ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(completion.id)
+    .bind(completion.campaign_id)
+    .bind(completion.user_id)
+    .bind(&completion.task_name)
+    .bind(&completion.details)
+    .bind(completion.created_at)
+    .execute(executor)
+    .await
+    .map_err(|err| match foreign_key_violation(&err) {
+        Some("task_completions_campaign_id_fkey") => ApiError::not_found("campaign not found"),
+        Some("task_completions_user_id_fkey") => ApiError::not_found("user not found"),
+        _ => ApiError::from_sqlx(err),
+    })?;
+
+    Ok(completion)
+}
+
+/// Checks a `complete_task` embedded in a proxy run: it must be for the caller, for a
+/// campaign they are targeted by, and name that campaign's `required_task`.
+pub fn validate_embedded_task(
+    user: &UserProfile,
+    request: &TaskCompletionRequest,
+    campaigns: &[Campaign],
+) -> ApiResult<()> {
+    if request.user_id != user.id {
+        return Err(ApiError::validation_field(
+            "complete_task.user_id",
+            "complete_task.user_id must match user_id",
+        ));
+    }
+
+    let campaign = campaigns
+        .iter()
+        .find(|campaign| campaign.id == request.campaign_id)
+        .ok_or_else(|| ApiError::not_found("campaign not found"))?;
+    if !user_matches_campaign(user, campaign).matched {
+        return Err(ApiError::validation_field(
+            "complete_task.campaign_id",
+            format!("user is not targeted by campaign '{}'", campaign.name),
+        ));
+    }
+    if request.task_name != campaign.required_task {
+        return Err(ApiError::validation_field(
+            "complete_task.task_name",
+            format!(
+                "campaign '{}' requires task '{}'",
+                campaign.name, campaign.required_task
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn require_management_key(config: &AppConfig, headers: &HeaderMap) -> ApiResult<()> {
+    let Some(expected) = config.management_api_key.as_deref() else {
+        return Err(ApiError::config(
+            "MANAGEMENT_API_KEY not configured; management endpoints are disabled",
+        ));
+    };
+
+    let provided =
+        bearer_token(headers).ok_or_else(|| ApiError::unauthorized("management key required"))?;
+
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(ApiError::unauthorized("invalid management key"));
+    }
+
+    Ok(())
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+pub fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+
+    left.iter()
+        .zip(right.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+/// Returns the violated constraint name when `err` is a Postgres foreign-key violation.
+pub fn foreign_key_violation(err: &sqlx::Error) -> Option<&str> {
+    let db_err = err.as_database_error()?;
+    if db_err.code().as_deref() != Some(FOREIGN_KEY_VIOLATION) {
+        return None;
+    }
+    db_err.constraint()
+}
+
+pub async fn check_database(db: Option<&PgPool>) -> DependencyStatus {
+    let Some(db) = db else {
+        return DependencyStatus::not_configured("set DATABASE_URL");
+    };
+
+    match sqlx::query_scalar::<_, i32>("select 1").fetch_one(db).await {
+        Ok(_) => DependencyStatus::up(None),
+        Err(err) => DependencyStatus::down(err.to_string()),
+    }
+}
+
+/// Records a settled user_direct payment. `user_id` is only linked when the profile exists,
+/// since paid tool runs do not require one.
+pub async fn record_user_direct_payment(
+    db: impl PgExecutor<'_>,
+    service: &str,
+    amount_cents: u64,
+    attribution: &PaymentAttribution,
+    payment: &VerifiedX402Payment,
+) -> ApiResult<()> {
+    let tx_hash = payment
+        .tx_hash
+        .clone()
+        .unwrap_or_else(|| format!("user-{}", Uuid::new_v4()));
+    let payer = payment
+        .payer
+        .clone()
+        .or_else(|| attribution.user_id.map(|user_id| user_id.to_string()))
+        .or_else(|| attribution.caller.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    sqlx::query(
+        r#"
+        insert into payments (
+            tx_hash, campaign_id, service, amount_cents, payer, source, status,
+            created_at, user_id, network, scheme, caller
+        )
+        values (
+            $1, null, $2, $3, $4, $5, $6, $7,
+            (select id from users where id = $8), $9, $10, $11
+        )
+        on conflict (tx_hash) do nothing
+        "#,
+    )
+    .bind(tx_hash)
+    .bind(service)
+    .bind(amount_cents as i64)
+    .bind(payer)
+    .bind(PaymentSource::User.as_db_str())
+    .bind(PaymentStatus::Settled.as_db_str())
+    .bind(Utc::now())
+    .bind(attribution.user_id)
+    .bind(&payment.network)
+    .bind(&payment.scheme)
+    .bind(attribution.caller.as_deref())
+    .execute(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    Ok(())
+}
+
+/// Resolves the x402 payment payload, preferring the `PAYMENT-SIGNATURE` header and falling
+/// back to a `payment` body field for clients that cannot set custom headers.
+pub fn payment_signature(headers: &HeaderMap, body_payment: Option<&str>) -> Option<String> {
+    headers
+        .get(PAYMENT_SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(body_payment)
+        .map(str::to_string)
+}
+
+/// Everything payment verification needs beyond the request itself.
+pub struct PaymentContext<'a> {
+    /// The facilitator client, `AppState::facilitator_http`.
+    pub http: &'a Client,
+    pub config: &'a AppConfig,
+    pub db: Option<&'a PgPool>,
+    pub metrics: &'a Metrics,
+    /// Set only when `X402_SETTLE_MODE=async` started the settlement worker.
+    pub settle_queue: Option<&'a Sender<SettlementJob>>,
+}
+
 pub async fn verify_x402_payment(
-    http: &Client,
-    config: &AppConfig,
+    ctx: &PaymentContext<'_>,
     service: &str,
     amount_cents: u64,
     resource_path: &str,
-    headers: &HeaderMap,
+    payment_signature: Option<&str>,
+    attribution: Option<PaymentAttribution>,
+    request_id: &str,
 ) -> ApiResult<VerifiedX402Payment> {
-    let Some(signature) = headers
-        .get(PAYMENT_SIGNATURE_HEADER)
-        .and_then(|value| value.to_str().ok())
-    else {
+    let config = ctx.config;
+    let Some(signature) = payment_signature else {
         return Err(payment_required_error(
             config,
             service,
@@ -102,23 +898,553 @@ pub async fn verify_x402_payment(
         ));
     };
 
-    let requirement = build_payment_requirement(config, service, amount_cents, resource_path)?;
-    match verify_and_settle_x402_payment(http, config, signature, &requirement).await {
+    let rejected = |err: ApiError| {
+        payment_required_error(
+            config,
+            service,
+            amount_cents,
+            resource_path,
+            format!("payment rejected: {err}"),
+            "regenerate PAYMENT-SIGNATURE from the latest challenge and retry",
+        )
+    };
+
+    let requirements = build_payment_requirements(config, service, amount_cents, resource_path)?;
+    if config.x402_test_mode && !signature.trim().is_empty() {
+        return test_mode_payment(signature, &requirements);
+    }
+    // A payload that does not decode, or does not fit the requirement, is a malformed request
+    // (`400`), not a payment the facilitator turned down.
+    let requirement = select_payment_requirement(signature, &requirements)?.clone();
+    validate_payment_signature(signature, &requirement)?;
+    let verified = match ctx.settle_queue {
+        Some(queue) => {
+            enqueue_settlement(
+                ctx,
+                queue,
+                SettlementJob {
+                    payment_signature: signature.to_string(),
+                    requirement: requirement.clone(),
+                    service: service.to_string(),
+                    amount_cents,
+                    attribution,
+                    verified_payer: None,
+                    request_id: request_id.to_string(),
+                },
+            )
+            .await
+        }
+        None => {
+            verify_and_settle_x402_payment(
+                ctx.http,
+                config,
+                ctx.metrics,
+                signature,
+                &requirement,
+                request_id,
+            )
+            .await
+        }
+    };
+
+    match verified {
         Ok(payment) => Ok(payment),
         Err(err) => match err {
             ApiError::Config { .. } => Err(err),
-            _ => Err(payment_required_error(
-                config,
-                service,
-                amount_cents,
-                resource_path,
-                format!("payment rejected: {err}"),
-                "regenerate PAYMENT-SIGNATURE from the latest challenge and retry",
-            )),
+            _ => {
+                if let ApiError::PaymentRejected { message } = &err {
+                    record_failed_payment(
+                        ctx,
+                        &requirement,
+                        service,
+                        amount_cents,
+                        signature,
+                        message,
+                    )
+                    .await;
+                }
+                Err(rejected(err))
+            }
         },
     }
 }
 
+/// `X402_TEST_MODE`: accepts the signature as a settled payment without contacting the
+/// facilitator. The payment is still recorded and counted by the caller like a real one.
+fn test_mode_payment(
+    signature: &str,
+    requirements: &[X402PaymentRequirement],
+) -> ApiResult<VerifiedX402Payment> {
+    // Pick the option the client named when the payload decodes; any other signature pays
+    // with the first one.
+    let requirement = select_payment_requirement(signature, requirements)
+        .ok()
+        .or(requirements.first())
+        .ok_or_else(|| ApiError::config("no x402 assets configured"))?;
+    let settlement = X402SettleResponse {
+        success: true,
+        transaction: Some(format!(
+            "{TEST_MODE_TX_HASH_PREFIX}{}",
+            Uuid::new_v4().simple()
+        )),
+        payer: Some(TEST_MODE_PAYER.to_string()),
+        error_reason: None,
+    };
+    warn!(
+        tx_hash = settlement.transaction.as_deref().unwrap_or_default(),
+        "X402_TEST_MODE accepted a payment without settling it"
+    );
+    let payment_response_header =
+        STANDARD
+            .encode(serde_json::to_vec(&settlement).map_err(|err| {
+                ApiError::internal(format!("failed to encode settlement: {err}"))
+            })?);
+
+    Ok(VerifiedX402Payment {
+        tx_hash: settlement.transaction,
+        payer: settlement.payer,
+        payment_response_header: Some(payment_response_header),
+        settlement_pending: false,
+        network: requirement.network.clone(),
+        scheme: requirement.scheme.clone(),
+    })
+}
+
+/// Verifies now and hands settlement to the background worker, so the caller is served
+/// before the payment is confirmed on-chain.
+async fn enqueue_settlement(
+    ctx: &PaymentContext<'_>,
+    queue: &Sender<SettlementJob>,
+    mut job: SettlementJob,
+) -> ApiResult<VerifiedX402Payment> {
+    let verify_response = verify_x402_signature(
+        ctx.http,
+        ctx.config,
+        ctx.metrics,
+        &job.payment_signature,
+        &job.requirement,
+        &job.request_id,
+    )
+    .await?;
+
+    let payer = verify_response.payer;
+    let network = job.requirement.network.clone();
+    let scheme = job.requirement.scheme.clone();
+    job.verified_payer = payer.clone();
+    let permit = match queue.try_reserve() {
+        Ok(permit) => permit,
+        // A full queue degrades to the synchronous path instead of growing without bound.
+        Err(mpsc::error::TrySendError::Full(())) => {
+            warn!(
+                request_id = %job.request_id,
+                "settlement queue is full; settling in the request"
+            );
+            return settle_in_request(ctx, job).await;
+        }
+        Err(mpsc::error::TrySendError::Closed(())) => {
+            return Err(ApiError::internal("settlement worker is not running"));
+        }
+    };
+    // The facilitator keeps verifying a signature until it settles, so the pending row is what
+    // stops a replay from being served again meanwhile.
+    if let Some(db) = ctx.db {
+        match insert_pending_payment(db, &job).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(ApiError::conflict(
+                    "PAYMENT-SIGNATURE is already being settled; sign a new payment",
+                ));
+            }
+            // Without the pending row a lost job could never be reconciled.
+            Err(err) => {
+                warn!(
+                    request_id = %job.request_id,
+                    "failed to record pending payment, settling in the request: {err}"
+                );
+                return settle_in_request(ctx, job).await;
+            }
+        }
+    }
+    permit.send(job);
+    ctx.metrics.settle_queue_depth.inc();
+
+    Ok(VerifiedX402Payment {
+        tx_hash: None,
+        payer,
+        payment_response_header: None,
+        settlement_pending: true,
+        network,
+        scheme,
+    })
+}
+
+/// Settles a verified job the way `X402_SETTLE_MODE=sync` would, for when it cannot be queued.
+async fn settle_in_request(
+    ctx: &PaymentContext<'_>,
+    job: SettlementJob,
+) -> ApiResult<VerifiedX402Payment> {
+    settle_x402_signature(
+        ctx.http,
+        ctx.config,
+        ctx.metrics,
+        &job.payment_signature,
+        &job.requirement,
+        &job.request_id,
+        job.verified_payer,
+    )
+    .await
+}
+
+/// Settles queued payments one at a time and records the outcome the way the synchronous
+/// path would have.
+pub fn spawn_settlement_worker(
+    http: Client,
+    config: AppConfig,
+    db: Option<PgPool>,
+    metrics: Metrics,
+) -> Sender<SettlementJob> {
+    let (sender, mut receiver) =
+        mpsc::channel::<SettlementJob>(config.x402_settle_queue_capacity as usize);
+
+    tokio::spawn(async move {
+        while let Some(job) = receiver.recv().await {
+            metrics.settle_queue_depth.dec();
+            let ctx = PaymentContext {
+                http: &http,
+                config: &config,
+                db: db.as_ref(),
+                metrics: &metrics,
+                settle_queue: None,
+            };
+
+            let settled = settle_x402_signature(
+                &http,
+                &config,
+                &metrics,
+                &job.payment_signature,
+                &job.requirement,
+                &job.request_id,
+                job.verified_payer.clone(),
+            )
+            .await;
+
+            finish_settlement(&ctx, &job, settled, 0).await;
+        }
+    });
+
+    sender
+}
+
+/// Keeps a queued settlement as a `pending` payments row until `finish_settlement` resolves it,
+/// so neither a restart nor a facilitator outage loses the payment. Returns false when the
+/// same payment is already pending.
+async fn insert_pending_payment(db: &PgPool, job: &SettlementJob) -> ApiResult<bool> {
+    let payer = job
+        .verified_payer
+        .clone()
+        .or_else(|| payment_attempt(&job.payment_signature).payer)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let inserted = sqlx::query(
+        r#"
+        insert into payments (
+            tx_hash, campaign_id, service, amount_cents, payer, source, status,
+            created_at, network, scheme, settlement_job
+        )
+        values ($1, null, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        on conflict (tx_hash) do nothing
+        "#,
+    )
+    .bind(pending_payment_key(&job.payment_signature))
+    .bind(&job.service)
+    .bind(job.amount_cents as i64)
+    .bind(payer)
+    .bind(PaymentSource::User.as_db_str())
+    .bind(PaymentStatus::Pending.as_db_str())
+    .bind(Utc::now())
+    .bind(&job.requirement.network)
+    .bind(&job.requirement.scheme)
+    .bind(sqlx::types::Json(job))
+    .execute(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+    Ok(inserted.rows_affected() > 0)
+}
+
+fn pending_payment_key(payment_signature: &str) -> String {
+    format!("pending-{}", payment_attempt_key(payment_signature))
+}
+
+/// Records a settle call's outcome the way the synchronous path would have and resolves the
+/// payment's `pending` row. `attempts` counts earlier settle calls for this payment. Returns
+/// `settled`, `failed`, or `retry` when the row stays pending for the reconciler.
+async fn finish_settlement(
+    ctx: &PaymentContext<'_>,
+    job: &SettlementJob,
+    settled: ApiResult<VerifiedX402Payment>,
+    attempts: i32,
+) -> &'static str {
+    let pending_key = pending_payment_key(&job.payment_signature);
+    let reason = match settled {
+        Ok(payment) => {
+            ctx.metrics
+                .payment_events_total
+                .with_label_values(&[
+                    PaymentMode::UserDirect.as_str(),
+                    PaymentStatus::Settled.as_db_str(),
+                ])
+                .inc();
+            if let Some(db) = ctx.db
+                && let Err(err) = record_settlement(db, job, &payment, &pending_key).await
+            {
+                warn!("failed to record settled payment: {err}");
+            }
+            return "settled";
+        }
+        Err(ApiError::PaymentRejected { message }) => message,
+        Err(err) if attempts + 1 >= MAX_SETTLE_ATTEMPTS => {
+            format!("gave up after {MAX_SETTLE_ATTEMPTS} settle attempts: {err}")
+        }
+        Err(err) => {
+            warn!(
+                request_id = %job.request_id,
+                "background settlement failed: {err}"
+            );
+            if let Some(db) = ctx.db
+                && let Err(err) = sqlx::query(
+                    "update payments set settle_attempts = $2, settle_claimed_at = null \
+                     where tx_hash = $1",
+                )
+                .bind(&pending_key)
+                .bind(attempts + 1)
+                .execute(db)
+                .await
+            {
+                warn!("failed to count settle attempt: {err}");
+            }
+            return "retry";
+        }
+    };
+
+    // A payment the worker and the reconciler both tried is only recorded by whichever
+    // resolves its pending row first.
+    let claimed = match ctx.db {
+        Some(db) => sqlx::query("delete from payments where tx_hash = $1 and status = 'pending'")
+            .bind(&pending_key)
+            .execute(db)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .unwrap_or_else(|err| {
+                warn!("failed to resolve pending payment: {err}");
+                false
+            }),
+        None => false,
+    };
+    if claimed {
+        record_failed_payment(
+            ctx,
+            &job.requirement,
+            &job.service,
+            job.amount_cents,
+            &job.payment_signature,
+            &reason,
+        )
+        .await;
+    }
+    "failed"
+}
+
+/// Replaces a pending row with the settled one. Only attributed payments keep a `settled`
+/// row, as on the synchronous path.
+async fn record_settlement(
+    db: &PgPool,
+    job: &SettlementJob,
+    payment: &VerifiedX402Payment,
+    pending_key: &str,
+) -> ApiResult<()> {
+    let mut tx = db.begin().await.map_err(ApiError::from_sqlx)?;
+    sqlx::query("delete from payments where tx_hash = $1 and status = 'pending'")
+        .bind(pending_key)
+        .execute(&mut *tx)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+    if let Some(attribution) = &job.attribution {
+        record_user_direct_payment(
+            &mut *tx,
+            &job.service,
+            job.amount_cents,
+            attribution,
+            payment,
+        )
+        .await?;
+    }
+    tx.commit().await.map_err(ApiError::from_sqlx)
+}
+
+/// Retries the settle call of payments left `pending` for longer than
+/// `x402_reconcile_after_secs`, e.g. because a restart dropped the in-memory queue or the
+/// facilitator was unreachable.
+pub fn spawn_settlement_reconciler(http: Client, config: AppConfig, db: PgPool, metrics: Metrics) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.x402_reconcile_interval_secs));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(err) = reconcile_pending_settlements(&http, &config, &db, &metrics).await {
+                warn!("settlement reconciliation failed: {err}");
+            }
+        }
+    });
+}
+
+/// One reconciliation pass; also refreshes `x402_pending_settlements`.
+pub async fn reconcile_pending_settlements(
+    http: &Client,
+    config: &AppConfig,
+    db: &PgPool,
+    metrics: &Metrics,
+) -> ApiResult<()> {
+    // Claimed in one statement so overlapping passes, here or on another instance, each get
+    // different rows. A claim as old as the stuck threshold is taken to be abandoned.
+    let stuck = sqlx::query_as::<_, PendingPaymentRow>(
+        r#"
+        update payments
+        set settle_claimed_at = now()
+        where tx_hash in (
+            select tx_hash
+            from payments
+            where status = 'pending'
+              and settlement_job is not null
+              and created_at < now() - make_interval(secs => $1)
+              and (settle_claimed_at is null
+                   or settle_claimed_at < now() - make_interval(secs => $1))
+            order by created_at
+            limit $2
+            for update skip locked
+        )
+        returning settlement_job, settle_attempts
+        "#,
+    )
+    .bind(config.x402_reconcile_after_secs as f64)
+    .bind(RECONCILE_BATCH_SIZE)
+    .fetch_all(db)
+    .await
+    .map_err(ApiError::from_sqlx)?;
+
+    let ctx = PaymentContext {
+        http,
+        config,
+        db: Some(db),
+        metrics,
+        settle_queue: None,
+    };
+    for row in stuck {
+        let job = row.settlement_job.0;
+        let settled = settle_x402_signature(
+            http,
+            config,
+            metrics,
+            &job.payment_signature,
+            &job.requirement,
+            &job.request_id,
+            job.verified_payer.clone(),
+        )
+        .await;
+        let outcome = finish_settlement(&ctx, &job, settled, row.settle_attempts).await;
+        metrics
+            .settlement_reconciliations_total
+            .with_label_values(&[outcome])
+            .inc();
+    }
+
+    let pending: i64 = sqlx::query_scalar("select count(*) from payments where status = 'pending'")
+        .fetch_one(db)
+        .await
+        .map_err(ApiError::from_sqlx)?;
+    metrics.pending_settlements.set(pending);
+    Ok(())
+}
+
+/// Identifies a payment attempt by its payload nonce, or by a hash of the whole signature when
+/// the payload carries none.
+fn payment_attempt_key(payment_signature: &str) -> String {
+    payment_attempt(payment_signature).nonce.unwrap_or_else(|| {
+        let mut hasher = Sha256::new();
+        hasher.update(payment_signature.as_bytes());
+        format!("sig-{}", hex_digest(hasher))
+    })
+}
+
+/// Keeps a `failed` payments row for a facilitator rejection. Rows are keyed by the payload
+/// nonce so client retries of the same authorization are recorded and counted once.
+async fn record_failed_payment(
+    ctx: &PaymentContext<'_>,
+    requirement: &X402PaymentRequirement,
+    service: &str,
+    amount_cents: u64,
+    payment_signature: &str,
+    reason: &str,
+) {
+    let Some(db) = ctx.db else {
+        return;
+    };
+
+    let attempt = payment_attempt(payment_signature);
+    let attempt_key = payment_attempt_key(payment_signature);
+
+    let inserted = sqlx::query(
+        r#"
+        insert into payments (
+            tx_hash, campaign_id, service, amount_cents, payer, source, status,
+            failure_reason, created_at, network, scheme
+        )
+        values ($1, null, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        on conflict (tx_hash) do nothing
+        "#,
+    )
+    .bind(format!("failed-{attempt_key}"))
+    .bind(service)
+    .bind(amount_cents as i64)
+    .bind(attempt.payer.unwrap_or_else(|| "unknown".to_string()))
+    .bind(PaymentSource::User.as_db_str())
+    .bind(PaymentStatus::Failed.as_db_str())
+    .bind(reason)
+    .bind(Utc::now())
+    .bind(&requirement.network)
+    .bind(&requirement.scheme)
+    .execute(db)
+    .await;
+
+    match inserted {
+        Ok(result) if result.rows_affected() > 0 => {
+            ctx.metrics
+                .payment_events_total
+                .with_label_values(&[
+                    PaymentMode::UserDirect.as_str(),
+                    PaymentStatus::Failed.as_db_str(),
+                ])
+                .inc();
+        }
+        Ok(_) => {}
+        Err(err) => warn!("failed to record rejected payment: {err}"),
+    }
+}
+
+/// The `428` for a user who matches `campaign` but has not done its `required_task`. The task
+/// and campaign are repeated in `details` so clients can prompt for it without parsing.
+pub fn task_required_error(campaign: &Campaign) -> ApiError {
+    ApiError::precondition(format!(
+        "complete sponsor task '{}' for campaign '{}' before sponsored usage",
+        campaign.required_task, campaign.name
+    ))
+    .with_details(serde_json::json!({
+        "required_task": campaign.required_task,
+        "campaign_id": campaign.id,
+        "campaign_name": campaign.name,
+    }))
+}
+
 pub fn payment_required_error(
     config: &AppConfig,
     service: &str,
@@ -127,33 +1453,38 @@ pub fn payment_required_error(
     message: impl Into<String>,
     next_step: impl Into<String>,
 ) -> ApiError {
-    let requirement = match build_payment_requirement(config, service, amount_cents, resource_path)
-    {
-        Ok(value) => value,
-        Err(err) => return err,
-    };
+    let requirements =
+        match build_payment_requirements(config, service, amount_cents, resource_path) {
+            Ok(value) => value,
+            Err(err) => return err,
+        };
 
-    let payment_required = match encode_payment_required_header(&requirement) {
+    let payment_required = match encode_payment_required_header(&requirements) {
         Ok(value) => value,
         Err(err) => return ApiError::internal(err),
     };
 
-    ApiError::PaymentRequired(PaymentRequired {
+    ApiError::PaymentRequired(Box::new(PaymentRequired {
         service: service.to_string(),
+        x402_version: config.x402_version.clone(),
         amount_cents,
         accepted_header: PAYMENT_SIGNATURE_HEADER.to_string(),
         payment_required,
+        requirements,
         message: message.into(),
         next_step: next_step.into(),
-    })
+        details: None,
+    }))
 }
 
-fn build_payment_requirement(
+/// One requirement per accepted asset: the primary `X402_ASSET` first, then
+/// `X402_EXTRA_ASSETS` in configured order.
+pub fn build_payment_requirements(
     config: &AppConfig,
     service: &str,
     amount_cents: u64,
     resource_path: &str,
-) -> ApiResult<X402PaymentRequirement> {
+) -> ApiResult<Vec<X402PaymentRequirement>> {
     let pay_to = required_non_empty_env_like(config.x402_pay_to.as_deref(), "X402_PAY_TO")?;
     let asset = required_non_empty_env_like(config.x402_asset.as_deref(), "X402_ASSET")?;
 
@@ -162,20 +1493,47 @@ fn build_payment_requirement(
         config.public_base_url.trim_end_matches('/'),
         resource_path
     );
+    let requirement = |network: &str, asset: &str, decimals: u32, pay_to: &str| {
+        let base_units = amount_to_base_units(amount_cents, decimals);
+        // Facilitators reject transfers below the asset's dust limit, so such a price could
+        // never be paid; surface the misconfiguration instead of an unpayable challenge.
+        if base_units < u128::from(config.min_charge_base_units) {
+            return Err(ApiError::config(format!(
+                "price of {amount_cents} cents for '{service}' is {base_units} base units of \
+                 {asset} on {network}, below MIN_CHARGE_BASE_UNITS ({})",
+                config.min_charge_base_units
+            )));
+        }
+        Ok(X402PaymentRequirement {
+            scheme: config.x402_scheme.as_str().to_string(),
+            network: network.to_string(),
+            max_amount_required: base_units.to_string(),
+            resource: resource.clone(),
+            description: format!("Access paid service '{service}'"),
+            mime_type: "application/json".to_string(),
+            pay_to: pay_to.to_string(),
+            max_timeout_seconds: 300,
+            asset: asset.to_string(),
+            output_schema: config.x402_output_schemas.get(service).cloned(),
+            extra: HashMap::new(),
+        })
+    };
 
-    Ok(X402PaymentRequirement {
-        scheme: "exact".to_string(),
-        network: config.x402_network.clone(),
-        max_amount_required: amount_to_base_units(amount_cents),
-        resource,
-        description: format!("Access paid service '{service}'"),
-        mime_type: "application/json".to_string(),
-        pay_to,
-        max_timeout_seconds: 300,
-        asset,
-        output_schema: None,
-        extra: HashMap::new(),
-    })
+    let mut requirements = vec![requirement(
+        &config.x402_network,
+        &asset,
+        DEFAULT_X402_ASSET_DECIMALS,
+        &pay_to,
+    )?];
+    for extra in &config.x402_extra_assets {
+        requirements.push(requirement(
+            &extra.network,
+            &extra.asset,
+            extra.decimals,
+            extra.pay_to.as_deref().unwrap_or(&pay_to),
+        )?);
+    }
+    Ok(requirements)
 }
 
 fn required_non_empty_env_like(value: Option<&str>, key: &str) -> ApiResult<String> {
@@ -189,49 +1547,256 @@ fn required_non_empty_env_like(value: Option<&str>, key: &str) -> ApiResult<Stri
     Ok(trimmed.to_string())
 }
 
-fn amount_to_base_units(amount_cents: u64) -> String {
-    (u128::from(amount_cents) * USDC_BASE_UNITS_PER_CENT).to_string()
+/// `decimals` is at least 2 (see `X402AcceptedAsset`), so a cent is a whole number of
+/// base units.
+fn amount_to_base_units(amount_cents: u64, decimals: u32) -> u128 {
+    u128::from(amount_cents) * 10u128.pow(decimals.saturating_sub(2))
 }
 
-fn encode_payment_required_header(requirement: &X402PaymentRequirement) -> Result<String, String> {
-    let bytes = serde_json::to_vec(&vec![requirement]).map_err(|err| err.to_string())?;
+fn encode_payment_required_header(
+    requirements: &[X402PaymentRequirement],
+) -> Result<String, String> {
+    let bytes = serde_json::to_vec(requirements).map_err(|err| err.to_string())?;
     Ok(STANDARD.encode(bytes))
 }
 
+/// `AppConfig::from_env` rejects versions that are not valid header values.
+pub fn insert_x402_version_header(headers: &mut HeaderMap, x402_version: &str) {
+    if let Ok(value) = HeaderValue::from_str(x402_version) {
+        headers.insert(HeaderName::from_static(X402_VERSION_HEADER), value);
+    }
+}
+
 pub fn build_paid_tool_response(
     service: String,
     request: ServiceRunRequest,
-    payment_mode: String,
-    sponsored_by: Option<String>,
+    payment_mode: PaymentMode,
+    campaign: Option<&Campaign>,
     tx_hash: Option<String>,
     payment_response_header: Option<&str>,
+    config: &AppConfig,
 ) -> Response {
+    let requester = match (&request.user_id, &request.caller) {
+        (Some(user_id), _) => format!("user {user_id}"),
+        (None, Some(caller)) => format!("caller {caller}"),
+        (None, None) => "anonymous caller".to_string(),
+    };
     let payload = ServiceRunResponse {
         service: service.clone(),
         output: format!(
-            "Executed '{}' task for user {} with input: {}",
-            service, request.user_id, request.input
+            "Executed '{}' task for {} with input: {}",
+            service, requester, request.input
         ),
         payment_mode,
-        sponsored_by,
+        sponsored_by: campaign.map(|campaign| campaign.sponsor.clone()),
+        campaign_id: campaign.map(|campaign| campaign.id),
         tx_hash,
     };
 
     let mut response = (StatusCode::OK, Json(payload)).into_response();
-    response.headers_mut().insert(
-        HeaderName::from_static(X402_VERSION_HEADER),
-        HeaderValue::from_static("2"),
-    );
-
+    insert_x402_version_header(response.headers_mut(), &config.x402_version);
     if let Some(payment_response) = payment_response_header {
-        if let Ok(header_value) = HeaderValue::from_str(payment_response) {
-            response.headers_mut().insert(
-                HeaderName::from_static(PAYMENT_RESPONSE_HEADER),
-                header_value,
-            );
+        insert_payment_response_headers(response.headers_mut(), config, payment_response);
+    }
+
+    response
+}
+
+/// Sets the facilitator's settlement response under every `payment_response_headers` name.
+pub fn insert_payment_response_headers(
+    headers: &mut HeaderMap,
+    config: &AppConfig,
+    payment_response: &str,
+) {
+    if let Ok(value) = HeaderValue::from_str(payment_response) {
+        for name in &config.payment_response_headers {
+            headers.insert(name.clone(), value.clone());
         }
     }
+}
+
+/// Reuses the caller's `x-request-id` when it is a sane header value so traces line up
+/// end to end; otherwise mints a fresh one for this request.
+pub fn request_id_from_headers(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+pub fn idempotency_key_from_headers(headers: &HeaderMap) -> ApiResult<Option<String>> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    let key = value
+        .to_str()
+        .map_err(|_| ApiError::validation("Idempotency-Key must be visible ASCII"))?
+        .trim();
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(ApiError::validation(format!(
+            "Idempotency-Key must be 1-{MAX_IDEMPOTENCY_KEY_LEN} characters"
+        )));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+/// `tx_hash` for a sponsored proxy payment. With an `Idempotency-Key` it is derived from the
+/// user, service, and key, so a retry lands on the first attempt's `payments` row even when a
+/// different campaign would be picked for it now. Without a key every run is a new payment.
+pub fn sponsored_tx_hash(user_id: Uuid, service: &str, idempotency_key: Option<&str>) -> String {
+    let Some(key) = idempotency_key else {
+        return format!("sponsor-{}", Uuid::new_v4().simple());
+    };
+    let mut hasher = Sha256::new();
+    for part in [user_id.to_string().as_str(), service, key] {
+        // Length-prefixed so no two different tuples hash the same bytes.
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part.as_bytes());
+    }
+    format!("sponsor-{}", hex_digest(hasher))
+}
+
+/// Hash of a sponsored proxy run's input, stored with a keyed payment so a replay of the key
+/// can be checked against the run it was first used for.
+pub fn sponsored_request_hash(input: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(input.as_bytes());
+    hex_digest(hasher)
+}
+
+/// Finds the sponsored proxy run already paid under `tx_hash` and returns the campaign that
+/// paid for it, so a retry is answered the same way without choosing a campaign again. `None`
+/// when the key has not been used. The key must have been used for the same input, within
+/// `SPONSORED_IDEMPOTENCY_KEY_TTL_SECS`.
+pub async fn find_sponsored_replay(
+    db: &PgPool,
+    tx_hash: &str,
+    request_hash: Option<&str>,
+) -> ApiResult<Option<Campaign>> {
+    let Some((stored_hash, expired, campaign_id)) =
+        sqlx::query_as::<_, (Option<String>, bool, Option<Uuid>)>(
+            r#"
+            select request_hash, created_at < now() - make_interval(secs => $2), campaign_id
+            from payments
+            where tx_hash = $1
+            "#,
+        )
+        .bind(tx_hash)
+        .bind(SPONSORED_IDEMPOTENCY_KEY_TTL_SECS as f64)
+        .fetch_optional(db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+    else {
+        return Ok(None);
+    };
 
+    if stored_hash.as_deref() != request_hash {
+        return Err(ApiError::conflict(
+            "Idempotency-Key was already used with a different input",
+        ));
+    }
+    if expired {
+        return Err(ApiError::conflict(
+            "Idempotency-Key has expired; send a new key",
+        ));
+    }
+    let row = sqlx::query_as::<_, CampaignRow>(&campaign_select("where id = $1"))
+        .bind(campaign_id)
+        .fetch_optional(db)
+        .await
+        .map_err(ApiError::from_sqlx)?
+        .ok_or_else(|| {
+            ApiError::conflict(
+                "the campaign that paid for this Idempotency-Key was deleted; send a new key",
+            )
+        })?;
+    Campaign::try_from(row)
+        .map(Some)
+        .map_err(|err| ApiError::database(StatusCode::INTERNAL_SERVER_ERROR, err))
+}
+
+/// Lowercase hex of a SHA-256 digest; unlike `DefaultHasher`, stable across Rust releases, so
+/// it is safe to persist.
+fn hex_digest(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Attribution for a direct tool payment; a blank `caller` is treated as absent.
+pub fn payment_attribution(request: &ServiceRunRequest) -> ApiResult<PaymentAttribution> {
+    let caller = request
+        .caller
+        .as_deref()
+        .map(str::trim)
+        .filter(|caller| !caller.is_empty());
+    if let Some(caller) = caller
+        && caller.chars().count() > MAX_CALLER_LEN
+    {
+        return Err(ApiError::validation_field(
+            "caller",
+            format!("caller must be at most {MAX_CALLER_LEN} characters"),
+        ));
+    }
+
+    Ok(PaymentAttribution {
+        user_id: request.user_id,
+        caller: caller.map(str::to_string),
+    })
+}
+
+/// A `200` with `ETag` and `Last-Modified` taken from `updated_at`, or an empty `304` when the
+/// request already has this version. `If-None-Match` is preferred: the `ETag` carries
+/// `updated_at` to the microsecond, while `If-Modified-Since` only compares whole seconds, so
+/// it is consulted only when no `If-None-Match` is sent.
+pub fn conditional_response<T: Serialize>(
+    headers: &HeaderMap,
+    updated_at: DateTime<Utc>,
+    body: T,
+) -> Response {
+    let etag = format!("\"{}\"", updated_at.timestamp_micros());
+    let not_modified = match headers.get(header::IF_NONE_MATCH) {
+        Some(value) => value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+        }),
+        None => headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .is_some_and(|since| updated_at.timestamp() <= since.timestamp()),
+    };
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        (StatusCode::OK, Json(body)).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    let last_modified = updated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    if let Ok(value) = HeaderValue::from_str(&last_modified) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response
+}
+
+pub fn created_response<T: Serialize>(location: &str, body: T) -> Response {
+    let mut response = (StatusCode::CREATED, Json(body)).into_response();
+    if let Ok(header_value) = HeaderValue::from_str(location) {
+        response
+            .headers_mut()
+            .insert(header::LOCATION, header_value);
+    }
     response
 }
 
@@ -247,21 +1812,360 @@ pub fn sponsored_api_service_key(api_id: Uuid) -> String {
     format!("{}-{}", SPONSORED_API_SERVICE_PREFIX, api_id)
 }
 
+/// Reserved platform services and the whole sponsored-api namespace are priced outside
+/// `service_price`, so they must not be reachable as ad-hoc tool services.
+pub fn is_reserved_service(service: &str) -> bool {
+    RESERVED_SERVICES.contains(&service)
+        || service
+            .strip_prefix(SPONSORED_API_SERVICE_PREFIX)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
+
+pub fn ensure_public_service(service: &str) -> ApiResult<()> {
+    if is_reserved_service(service) {
+        return Err(ApiError::validation_field(
+            "service",
+            format!("service {service} is reserved"),
+        ));
+    }
+    Ok(())
+}
+
 pub fn normalize_upstream_method(method: Option<String>) -> ApiResult<String> {
     let value = method.unwrap_or_else(|| "POST".to_string());
     let normalized = value.trim().to_uppercase();
     match normalized.as_str() {
         "GET" | "POST" => Ok(normalized),
-        _ => Err(ApiError::validation("upstream_method must be GET or POST")),
+        _ => Err(ApiError::validation_field(
+            "upstream_method",
+            "upstream_method must be GET or POST",
+        )),
+    }
+}
+
+/// Trims and uppercases a profile region so `us` and ` US ` target the same campaigns, then
+/// checks it against `ALLOWED_REGIONS` when that is configured.
+pub fn normalize_region(config: &AppConfig, region: &str) -> ApiResult<String> {
+    let region = region.trim().to_ascii_uppercase();
+    if region.is_empty() {
+        return Err(ApiError::validation_field("region", "region is required"));
+    }
+    if let Some(allowed) = &config.allowed_regions
+        && !allowed.contains(&region)
+    {
+        return Err(ApiError::validation_field(
+            "region",
+            format!(
+                "region {region} is not allowed; expected one of {}",
+                allowed.join(", ")
+            ),
+        ));
+    }
+
+    Ok(region)
+}
+
+pub fn normalize_email(email: &str) -> ApiResult<String> {
+    let email = email.trim().to_lowercase();
+    if email.is_empty() {
+        return Err(ApiError::validation_field("email", "email is required"));
+    }
+    Ok(email)
+}
+
+/// Rejects a normalized email whose domain, or a parent of it, is in `BLOCKED_EMAIL_DOMAINS`.
+/// Only checked when a profile is created, so existing profiles can still be looked up.
+pub fn check_email_domain(config: &AppConfig, email: &str) -> ApiResult<()> {
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        return Ok(());
+    };
+    let blocked = config.blocked_email_domains.iter().any(|blocked| {
+        domain == blocked
+            || domain
+                .strip_suffix(blocked.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    });
+    if blocked {
+        return Err(ApiError::validation_field(
+            "email",
+            format!("email addresses at {domain} cannot register"),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn find_user_by_email(db: &PgPool, email: &str) -> ApiResult<Option<UserProfile>> {
+    sqlx::query_as::<_, UserProfile>(
+        r#"
+        select id, email, region, roles, tools_used, attributes, created_at
+        from users
+        where lower(btrim(email)) = $1
+        "#,
+    )
+    .bind(email)
+    .fetch_optional(db)
+    .await
+    .map_err(ApiError::from_sqlx)
+}
+
+/// Validates a `POST /profiles` body and builds the profile to insert.
+pub fn new_user_profile(config: &AppConfig, payload: CreateUserRequest) -> ApiResult<UserProfile> {
+    let email = normalize_email(&payload.email)?;
+    check_email_domain(config, &email)?;
+    let region = normalize_region(config, &payload.region)?;
+
+    Ok(UserProfile {
+        id: Uuid::new_v4(),
+        email,
+        region,
+        roles: payload.roles,
+        tools_used: payload.tools_used,
+        attributes: payload.attributes,
+        created_at: Utc::now(),
+    })
+}
+
+/// Creates one row of a profile batch. Nothing here fails the whole batch: a malformed row, a
+/// rejected email, and a taken email are all reported on the row.
+pub async fn create_batch_profile(
+    db: &PgPool,
+    config: &AppConfig,
+    index: usize,
+    row: Value,
+) -> ProfileBatchResult {
+    let created = async {
+        let payload = serde_json::from_value::<CreateUserRequest>(row)
+            .map_err(|err| ApiError::validation(format!("invalid profile: {err}")))?;
+        let profile = new_user_profile(config, payload)?;
+        insert_user_profile(db, profile)
+            .await?
+            .ok_or_else(|| ApiError::conflict("a profile with this email already exists"))
+    }
+    .await;
+
+    match created {
+        Ok(profile) => ProfileBatchResult {
+            index,
+            status: StatusCode::CREATED.as_u16(),
+            user_id: Some(profile.id),
+            error: None,
+        },
+        Err(err) => {
+            let (status, body) = err.status_and_body();
+            ProfileBatchResult {
+                index,
+                status: status.as_u16(),
+                user_id: None,
+                error: Some(body),
+            }
+        }
     }
 }
 
-pub async fn call_upstream(
+/// Inserts a profile, returning `None` when its email is already taken.
+pub async fn insert_user_profile(
+    db: &PgPool,
+    profile: UserProfile,
+) -> ApiResult<Option<UserProfile>> {
+    sqlx::query_as::<_, UserProfile>(
+        r#"
+        insert into users (id, email, region, roles, tools_used, attributes, created_at)
+        values ($1, $2, $3, $4, $5, $6, $7)
+        on conflict ((lower(btrim(email)))) do nothing
+        returning id, email, region, roles, tools_used, attributes, created_at
+        "#,
+    )
+    .bind(profile.id)
+    .bind(profile.email)
+    .bind(profile.region)
+    .bind(profile.roles)
+    .bind(profile.tools_used)
+    .bind(sqlx::types::Json(profile.attributes))
+    .bind(profile.created_at)
+    .fetch_optional(db)
+    .await
+    .map_err(ApiError::from_sqlx)
+}
+
+/// Converts an amount for a Postgres `bigint` column, which would otherwise wrap negative above
+/// `i64::MAX` and only fail when the row is read back.
+pub fn cents_to_i64(field: &str, cents: u64) -> ApiResult<i64> {
+    i64::try_from(cents).map_err(|_| {
+        ApiError::validation_field(field, format!("{field} must be at most {}", i64::MAX))
+    })
+}
+
+pub fn validate_price_cents(config: &AppConfig, field: &str, cents: u64) -> ApiResult<()> {
+    if cents < config.min_price_cents || cents > config.max_price_cents {
+        return Err(ApiError::validation_field(
+            field,
+            format!(
+                "{field} must be between {} and {}",
+                config.min_price_cents, config.max_price_cents
+            ),
+        ));
+    }
+    cents_to_i64(field, cents)?;
+
+    Ok(())
+}
+
+/// Checks each configured upstream header and bounds how many a sponsor may store, since
+/// every one is replayed on each upstream call.
+pub fn validate_upstream_headers(
+    config: &AppConfig,
+    headers: &HashMap<String, String>,
+) -> ApiResult<()> {
+    if headers.len() as u64 > config.max_upstream_headers {
+        return Err(ApiError::validation(format!(
+            "upstream_headers may contain at most {} headers",
+            config.max_upstream_headers
+        )));
+    }
+
+    let total_bytes: usize = headers
+        .iter()
+        .map(|(header, value)| header.len() + value.len())
+        .sum();
+    if total_bytes as u64 > config.max_upstream_headers_bytes {
+        return Err(ApiError::validation(format!(
+            "upstream_headers may total at most {} bytes",
+            config.max_upstream_headers_bytes
+        )));
+    }
+
+    // Every bad name and value is reported at once; values are never echoed back since they
+    // often hold credentials.
+    let mut names: Vec<&String> = headers.keys().collect();
+    names.sort();
+    let mut errors = Vec::new();
+    for header in names {
+        if HeaderName::from_bytes(header.as_bytes()).is_err() {
+            errors.push(UpstreamHeaderError {
+                header: header.clone(),
+                part: "name",
+                message: format!("invalid upstream header: {header}"),
+            });
+        }
+        if HeaderValue::from_str(&headers[header]).is_err() {
+            errors.push(UpstreamHeaderError {
+                header: header.clone(),
+                part: "value",
+                message: format!("invalid upstream header value for: {header}"),
+            });
+        }
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let message = errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    Err(
+        ApiError::validation(message).with_details(serde_json::json!({
+            "field": "upstream_headers",
+            "errors": errors,
+        })),
+    )
+}
+
+/// Lowercases and dedupes the header names a sponsored API forwards from callers, rejecting
+/// names that are not valid headers or are connection-scoped.
+pub fn normalize_forward_headers(headers: Vec<String>) -> ApiResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(headers.len());
+    for header in headers {
+        let name = header.trim().to_ascii_lowercase();
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(ApiError::validation_field(
+                "forward_headers",
+                format!("invalid forward header: {header}"),
+            ));
+        }
+        if HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+            return Err(ApiError::validation_field(
+                "forward_headers",
+                format!("hop-by-hop header cannot be forwarded: {header}"),
+            ));
+        }
+        if !normalized.contains(&name) {
+            normalized.push(name);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Sorts and dedupes the upstream statuses a sponsored API is billed for.
+pub fn normalize_success_status_codes(codes: Vec<u16>) -> ApiResult<Vec<u16>> {
+    if let Some(code) = codes.iter().find(|code| !(100..=599).contains(*code)) {
+        return Err(ApiError::validation_field(
+            "success_status_codes",
+            format!("invalid HTTP status code: {code}"),
+        ));
+    }
+    let mut codes = codes;
+    codes.sort_unstable();
+    codes.dedup();
+    Ok(codes)
+}
+
+/// Lowercases and dedupes the media types a sponsored API may relay, e.g. `application/json`
+/// or `text/*`.
+pub fn normalize_content_types(content_types: Vec<String>) -> ApiResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(content_types.len());
+    for content_type in content_types {
+        let value = content_type.trim().to_ascii_lowercase();
+        let valid = value.split_once('/').is_some_and(|(kind, subtype)| {
+            !kind.is_empty() && kind != "*" && !subtype.is_empty() && !value.contains(';')
+        });
+        if !valid {
+            return Err(ApiError::validation_field(
+                "allowed_response_content_types",
+                format!("invalid media type: {content_type}"),
+            ));
+        }
+        if !normalized.contains(&value) {
+            normalized.push(value);
+        }
+    }
+    Ok(normalized)
+}
+
+pub fn ensure_allowed_content_type(
+    api: &SponsoredApi,
+    response: &reqwest::Response,
+) -> ApiResult<()> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    if api.allows_content_type(content_type) {
+        return Ok(());
+    }
+
+    Err(ApiError::upstream(
+        StatusCode::BAD_GATEWAY,
+        format!(
+            "upstream responded with content-type {} which this sponsored API does not allow",
+            content_type.unwrap_or("(none)")
+        ),
+    ))
+}
+
+/// Sends the upstream request and returns as soon as response headers arrive, leaving the
+/// body unread so callers can either buffer or stream it. Redirects are only followed when the
+/// API sets `follow_redirects`, and then at most `max_redirects` of them.
+pub async fn send_upstream(
     http: &Client,
     api: &SponsoredApi,
     payload: Value,
+    incoming_headers: &HeaderMap,
     timeout_secs: u64,
-) -> ApiResult<(u16, String)> {
+    max_redirects: u64,
+    request_id: &str,
+) -> ApiResult<reqwest::Response> {
     let method = match api.upstream_method.as_str() {
         "GET" => Method::GET,
         "POST" => Method::POST,
@@ -276,10 +2180,34 @@ pub async fn call_upstream(
         .request(method.clone(), &api.upstream_url)
         .timeout(Duration::from_secs(timeout_secs));
 
+    // Sponsor-configured headers win so an upstream can keep its own correlation scheme.
+    if !api
+        .upstream_headers
+        .keys()
+        .any(|header| header.eq_ignore_ascii_case(REQUEST_ID_HEADER))
+    {
+        request = request.header(REQUEST_ID_HEADER, request_id);
+    }
+
     for (header, value) in &api.upstream_headers {
         request = request.header(header, value);
     }
 
+    // Only whitelisted caller headers pass through, and never over a sponsor-configured one.
+    for name in &api.forward_headers {
+        if HOP_BY_HOP_HEADERS.contains(&name.as_str())
+            || api
+                .upstream_headers
+                .keys()
+                .any(|header| header.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        for value in incoming_headers.get_all(name.as_str()) {
+            request = request.header(name.as_str(), value.clone());
+        }
+    }
+
     if matches!(method, Method::GET) {
         if let Some(params) = payload.as_object() {
             request = request.query(params);
@@ -288,12 +2216,123 @@ pub async fn call_upstream(
         request = request.json(&payload);
     }
 
-    let response = request
-        .send()
-        .await
+    let request = request
+        .build()
         .map_err(|err| ApiError::upstream(StatusCode::BAD_GATEWAY, err.to_string()))?;
+    let max_redirects = if api.follow_redirects {
+        max_redirects
+    } else {
+        0
+    };
+    execute_following_redirects(http, request, max_redirects).await
+}
+
+/// Runs `request`, following up to `max_redirects` redirects; with `0` a redirect is returned
+/// as the response. Each hop must pass the webhook URL check before it is requested, and is
+/// sent to the addresses that passed, so an upstream cannot bounce the call to an internal
+/// address. The request's timeout covers all hops together: each one gets only what is left.
+async fn execute_following_redirects(
+    http: &Client,
+    mut request: reqwest::Request,
+    max_redirects: u64,
+) -> ApiResult<reqwest::Response> {
+    let upstream_error = |message: String| ApiError::upstream(StatusCode::BAD_GATEWAY, message);
+    let timed_out = || upstream_error("upstream timed out while following redirects".to_string());
+    let deadline = request.timeout().map(|timeout| Instant::now() + *timeout);
+    let remaining = || match deadline {
+        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(Some(left)),
+            _ => Err(timed_out()),
+        },
+        None => Ok(None),
+    };
+    let mut followed = 0;
+    let mut hop_client: Option<Client> = None;
+    loop {
+        let replay = request.try_clone();
+        let response = hop_client
+            .as_ref()
+            .unwrap_or(http)
+            .execute(request)
+            .await
+            .map_err(|err| upstream_error(err.to_string()))?;
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .filter(|_| max_redirects > 0 && response.status().is_redirection())
+            .and_then(|value| value.to_str().ok());
+        let Some(location) = location else {
+            return Ok(response);
+        };
+        if followed == max_redirects {
+            return Err(upstream_error(format!(
+                "upstream redirected more than {max_redirects} times"
+            )));
+        }
+
+        let target = response.url().join(location).map_err(|_| {
+            upstream_error(format!(
+                "upstream redirected to an invalid location: {location}"
+            ))
+        })?;
+        let pinned = pinned_public_client(&target);
+        let pinned = match remaining()? {
+            Some(left) => tokio::time::timeout(left, pinned)
+                .await
+                .map_err(|_| timed_out())?,
+            None => pinned.await,
+        };
+        hop_client = Some(pinned.map_err(|reason| {
+            upstream_error(format!(
+                "upstream redirect to {target} refused: the target {reason}"
+            ))
+        })?);
+        let mut next = replay.ok_or_else(|| {
+            upstream_error("upstream request body cannot be resent after a redirect".to_string())
+        })?;
 
-    let status = response.status().as_u16();
-    let body = response.text().await.unwrap_or_default();
-    Ok((status, body))
+        // As browsers do, 301, 302, and 303 turn anything but GET or HEAD into a bodyless GET.
+        if matches!(response.status().as_u16(), 301..=303)
+            && !matches!(*next.method(), Method::GET | Method::HEAD)
+        {
+            *next.method_mut() = Method::GET;
+            *next.body_mut() = None;
+            next.headers_mut().remove(header::CONTENT_TYPE);
+            next.headers_mut().remove(header::CONTENT_LENGTH);
+        }
+        // Sponsor-configured and forwarded headers may carry credentials, so they only go to
+        // the upstream's own origin.
+        if target.origin() != next.url().origin() {
+            let kept: Vec<_> = [REQUEST_ID_HEADER, header::CONTENT_TYPE.as_str()]
+                .into_iter()
+                .filter_map(|name| {
+                    let value = next.headers().get(name)?.clone();
+                    Some((HeaderName::from_static(name), value))
+                })
+                .collect();
+            next.headers_mut().clear();
+            next.headers_mut().extend(kept);
+        }
+        *next.timeout_mut() = remaining()?;
+        *next.url_mut() = target;
+        request = next;
+        followed += 1;
+    }
+}
+
+pub fn streamed_upstream_response(upstream: reqwest::Response) -> Response {
+    let status =
+        StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = upstream.headers().get(header::CONTENT_TYPE).cloned();
+
+    let mut response = Response::new(Body::from_stream(upstream.bytes_stream()));
+    *response.status_mut() = status;
+    if let Some(content_type) = content_type
+        && let Ok(header_value) = HeaderValue::from_bytes(content_type.as_bytes())
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, header_value);
+    }
+    response
 }